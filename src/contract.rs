@@ -1,15 +1,29 @@
 use cosmwasm_std::{
-    attr, coin, to_binary, Addr, BankMsg, Binary, CosmosMsg, Deps, DepsMut, Env, MessageInfo,
-    Order, Response, StdResult,
+    attr, coin, from_binary, to_binary, to_vec, Addr, BankMsg, Binary, BlockInfo, Coin,
+    ContractResult, CosmosMsg, Deps, DepsMut, Empty, Env, MessageInfo, Order, QueryRequest, Reply,
+    Response, StdError, StdResult, Storage, SubMsg, SystemResult, WasmMsg, WasmQuery,
 };
 use cosmwasm_std::{entry_point, Uint128};
 
 use crate::error::ContractError;
-use crate::helper::extract_budget_coin;
-use crate::matching::{calculate_clr, QuadraticFundingAlgorithm, RawGrant};
-use crate::msg::{AllProposalsResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{proposal_seq, Config, Proposal, Vote, CONFIG, PROPOSALS, VOTES};
+#[cfg(feature = "stargate")]
+use crate::helper::results_hook_msg;
+use crate::helper::{extract_budget_coin, remaining, results_commitment, tally_hash};
+use crate::matching::{apply_tally, calculate_clr, QuadraticFundingAlgorithm, RawGrant};
+use crate::math::{apportion, ratio, sqrt};
+use crate::msg::{
+    AlgorithmInfoResponse, AllProposalsResponse, AttestedResponse, CohortWindowRemaining,
+    ContributionResponse, ExecuteMsg, FirstSeenResponse, InstantiateMsg, KycQueryMsg,
+    NotifierExecuteMsg, OperatorOverviewResponse, PoolUtilizationResponse, ProjectRegistryQueryMsg,
+    ProjectResponse, QueryMsg, SolvencyResponse, TenureOracleQueryMsg,
+};
+use crate::state::{
+    proposal_seq, Cohort, Config, Proposal, ProposalAggregate, RoundPhase, TallyAllocation,
+    TallyConfig, TenureWeighting, Vote, COHORTS, CONFIG, DISTRIBUTED, FIRST_SEEN, NOTIFIED_PHASES,
+    PROPOSALS, PROPOSAL_AGG, STIPEND_RESERVE, STIPEND_SPENT, TALLY, VOTER_COHORT, VOTES,
+};
 use cosmwasm_storage::nextval;
+use cw_utils::{Expiration, Scheduled};
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -20,7 +34,7 @@ pub fn instantiate(
 ) -> Result<Response, ContractError> {
     msg.validate(env)?;
 
-    let budget = extract_budget_coin(info.funds.as_slice(), &msg.budget_denom)?;
+    let mut budget = extract_budget_coin(info.funds.as_slice(), &msg.budget_denom)?;
     let mut create_proposal_whitelist: Option<Vec<Addr>> = None;
     let mut vote_proposal_whitelist: Option<Vec<Addr>> = None;
     if let Some(pwl) = msg.create_proposal_whitelist {
@@ -37,42 +51,219 @@ pub fn instantiate(
         }
         vote_proposal_whitelist = Some(tmp_wl);
     }
+    // voter stipends are reserved out of the sent budget
+    if let (Some(stipend), Some(vwl)) = (msg.voter_stipend, &vote_proposal_whitelist) {
+        let reserve = stipend.checked_mul(Uint128::new(vwl.len() as u128))?;
+        budget.amount = budget
+            .amount
+            .checked_sub(reserve)
+            .map_err(|_| ContractError::StipendExceedsBudget {})?;
+        STIPEND_RESERVE.save(deps.storage, &reserve)?;
+    }
+    let mut tenure_weighting: Option<TenureWeighting> = None;
+    if let Some(tw) = msg.tenure_weighting {
+        tenure_weighting = Some(TenureWeighting {
+            oracle: tw.oracle.map(|o| deps.api.addr_validate(&o)).transpose()?,
+            maturity_blocks: tw.maturity_blocks,
+        });
+    }
     let cfg = Config {
         admin: deps.api.addr_validate(&msg.admin)?,
         leftover_addr: deps.api.addr_validate(&msg.leftover_addr)?,
+        sponsor: info.sender,
         create_proposal_whitelist,
         vote_proposal_whitelist,
         voting_period: msg.voting_period,
         proposal_period: msg.proposal_period,
         algorithm: msg.algorithm,
         budget,
+        results_hook: msg.results_hook,
+        tenure_weighting,
+        vote_snapshot: msg.vote_snapshot,
+        project_registry: msg
+            .project_registry
+            .map(|r| deps.api.addr_validate(&r))
+            .transpose()?,
+        voter_stipend: msg.voter_stipend,
+        tally: msg
+            .tally
+            .map(|t| -> StdResult<_> {
+                Ok(TallyConfig {
+                    authority: deps.api.addr_validate(&t.authority)?,
+                    tally_hash: t.tally_hash,
+                })
+            })
+            .transpose()?,
+        notifier: msg
+            .notifier
+            .map(|n| deps.api.addr_validate(&n))
+            .transpose()?,
+        split_payouts: msg.split_payouts,
+        claim_deadline: msg.claim_deadline,
+        kyc_registry: msg
+            .kyc_registry
+            .map(|r| deps.api.addr_validate(&r))
+            .transpose()?,
     };
     CONFIG.save(deps.storage, &cfg)?;
 
     Ok(Response::default())
 }
 
+// reply id of the phase change notifications
+const NOTIFY_REPLY_ID: u64 = 1;
+// gas available to each notification, without a limit running out of gas
+// aborts the whole transaction instead of reaching reply
+const NOTIFY_GAS_LIMIT: u64 = 200_000;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
-    match msg {
+    // record first interaction height when tenure is not provided by an oracle
+    let config = CONFIG.load(deps.storage)?;
+    if let Some(TenureWeighting { oracle: None, .. }) = config.tenure_weighting {
+        let first_seen = FIRST_SEEN.key(&info.sender);
+        if first_seen.may_load(deps.storage)?.is_none() {
+            first_seen.save(deps.storage, &env.block.height)?;
+        }
+    }
+
+    let res = match msg {
         ExecuteMsg::CreateProposal {
             title,
             description,
             metadata,
             fund_address,
-        } => execute_create_proposal(deps, env, info, title, description, metadata, fund_address),
-        ExecuteMsg::VoteProposal { proposal_id } => {
-            execute_vote_proposal(deps, env, info, proposal_id)
+            registry_project_id,
+            opt_out_matching,
+        } => execute_create_proposal(
+            deps.branch(),
+            env.clone(),
+            info,
+            title,
+            description,
+            metadata,
+            fund_address,
+            registry_project_id,
+            opt_out_matching,
+        ),
+        ExecuteMsg::VoteProposal {
+            proposal_id,
+            stipend_amount,
+        } => execute_vote_proposal(
+            deps.branch(),
+            env.clone(),
+            info,
+            proposal_id,
+            stipend_amount,
+        ),
+        ExecuteMsg::TriggerDistribution { .. } => {
+            execute_trigger_distribution(deps.branch(), env.clone(), info)
+        }
+        // first interaction is recorded above
+        ExecuteMsg::Register {} => Ok(Response::new().add_attribute("action", "register")),
+        ExecuteMsg::SubmitTally {
+            allocations,
+            tally_hash,
+        } => execute_submit_tally(deps.branch(), env.clone(), info, allocations, tally_hash),
+        ExecuteMsg::RegisterFundAddress {
+            proposal_id,
+            fund_address,
+        } => execute_register_fund_address(
+            deps.branch(),
+            env.clone(),
+            info,
+            proposal_id,
+            fund_address,
+        ),
+        ExecuteMsg::ReduceBudget { amount } => execute_reduce_budget(deps.branch(), info, amount),
+        ExecuteMsg::SetCohort {
+            name,
+            members,
+            voting_start,
+            voting_period,
+        } => execute_set_cohort(
+            deps.branch(),
+            env.clone(),
+            info,
+            name,
+            members,
+            voting_start,
+            voting_period,
+        ),
+    }?;
+
+    // notify phase changes lazily, on the first transaction after each boundary
+    match &config.notifier {
+        Some(notifier) => notify_phase_changes(deps, &env, &config, notifier, res),
+        None => Ok(res),
+    }
+}
+
+fn notify_phase_changes(
+    deps: DepsMut,
+    env: &Env,
+    config: &Config,
+    notifier: &Addr,
+    res: Response,
+) -> Result<Response, ContractError> {
+    let mut reached = vec![];
+    if config.proposal_period.is_expired(&env.block) {
+        reached.push(RoundPhase::ProposalPeriodEnded);
+    }
+    if voting_ended(deps.storage, config, &env.block)? {
+        reached.push(RoundPhase::VotingEnded);
+    }
+    if DISTRIBUTED.may_load(deps.storage)?.unwrap_or(false) {
+        reached.push(RoundPhase::DistributionFinalized);
+    }
+
+    let mut notified = NOTIFIED_PHASES.may_load(deps.storage)?.unwrap_or_default();
+    let mut msgs = vec![];
+    for phase in reached {
+        if notified.contains(&phase) {
+            continue;
         }
-        ExecuteMsg::TriggerDistribution { .. } => execute_trigger_distribution(deps, env, info),
+        // a failing notifier must not block the round
+        msgs.push(
+            SubMsg::reply_on_error(
+                WasmMsg::Execute {
+                    contract_addr: notifier.to_string(),
+                    msg: to_binary(&NotifierExecuteMsg::PhaseChanged {
+                        phase: phase.clone(),
+                    })?,
+                    funds: vec![],
+                },
+                NOTIFY_REPLY_ID,
+            )
+            .with_gas_limit(NOTIFY_GAS_LIMIT),
+        );
+        notified.push(phase);
+    }
+    if msgs.is_empty() {
+        return Ok(res);
+    }
+    NOTIFIED_PHASES.save(deps.storage, &notified)?;
+
+    Ok(res.add_submessages(msgs))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        // notifier failures are recorded and ignored
+        NOTIFY_REPLY_ID => Ok(Response::new()
+            .add_attribute("action", "notify_failed")
+            .add_attribute("error", msg.result.unwrap_err())),
+        id => Err(ContractError::UnknownReplyId { id }),
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_create_proposal(
     deps: DepsMut,
     env: Env,
@@ -80,7 +271,9 @@ pub fn execute_create_proposal(
     title: String,
     description: String,
     metadata: Option<Binary>,
-    fund_address: String,
+    fund_address: Option<String>,
+    registry_project_id: Option<String>,
+    opt_out_matching: bool,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
@@ -96,14 +289,48 @@ pub fn execute_create_proposal(
         return Err(ContractError::ProposalPeriodExpired {});
     }
 
+    // verify project identity against the registry
+    if let Some(project_id) = &registry_project_id {
+        let registry = config
+            .project_registry
+            .ok_or(ContractError::ProjectRegistryNotConfigured {})?;
+        let request: QueryRequest<Empty> = QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: registry.to_string(),
+            msg: to_binary(&ProjectRegistryQueryMsg::Project {
+                id: project_id.clone(),
+            })?,
+        });
+        // the registry errors on unknown projects, an unreachable registry is a system error
+        let project: Option<ProjectResponse> = match deps.querier.raw_query(&to_vec(&request)?) {
+            SystemResult::Err(err) => {
+                return Err(StdError::generic_err(format!("Querier system error: {}", err)).into())
+            }
+            SystemResult::Ok(ContractResult::Err(_)) => None,
+            SystemResult::Ok(ContractResult::Ok(res)) => Some(from_binary(&res)?),
+        };
+        match project {
+            Some(p) if p.id == *project_id => {}
+            _ => {
+                return Err(ContractError::ProjectNotRegistered {
+                    id: project_id.clone(),
+                })
+            }
+        }
+    }
+
     let id = nextval(&mut proposal_seq(deps.storage))?;
     let p = Proposal {
         id,
         title: title.clone(),
         description,
         metadata,
-        fund_address: deps.api.addr_validate(&fund_address)?,
+        creator: info.sender,
+        fund_address: fund_address
+            .map(|a| deps.api.addr_validate(&a))
+            .transpose()?,
         collected_funds: Uint128::zero(),
+        registry_project_id,
+        opt_out_matching,
     };
     PROPOSALS.save(deps.storage, id.into(), &p)?;
 
@@ -113,28 +340,103 @@ pub fn execute_create_proposal(
         .add_attribute("proposal_id", id.to_string()))
 }
 
+pub fn execute_register_fund_address(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+    fund_address: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut proposal = PROPOSALS
+        .may_load(deps.storage, proposal_id)?
+        .ok_or(ContractError::ProposalNotFound {})?;
+
+    // only the creator can claim a nominated proposal
+    if info.sender != proposal.creator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if proposal.fund_address.is_some() {
+        return Err(ContractError::FundAddressAlreadyRegistered {});
+    }
+
+    // unclaimed allocations are already sent to the leftover address
+    if DISTRIBUTED.may_load(deps.storage)?.unwrap_or(false) {
+        return Err(ContractError::AlreadyDistributed {});
+    }
+
+    if let Some(deadline) = config.claim_deadline {
+        if deadline.is_expired(&env.block) {
+            return Err(ContractError::ClaimDeadlineExpired {});
+        }
+    }
+
+    let fund_address = deps.api.addr_validate(&fund_address)?;
+    if let Some(registry) = config.kyc_registry {
+        let res: AttestedResponse = deps.querier.query_wasm_smart(
+            registry,
+            &KycQueryMsg::Attested {
+                address: fund_address.to_string(),
+            },
+        )?;
+        if !res.attested {
+            return Err(ContractError::FundAddressNotAttested {
+                address: fund_address.to_string(),
+            });
+        }
+    }
+
+    proposal.fund_address = Some(fund_address.clone());
+    PROPOSALS.save(deps.storage, proposal_id, &proposal)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "register_fund_address")
+        .add_attribute("proposal_id", proposal_id.to_string())
+        .add_attribute("fund_address", fund_address))
+}
+
 pub fn execute_vote_proposal(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     proposal_id: u64,
+    stipend_amount: Option<Uint128>,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
     // check whitelist
-    if let Some(wl) = config.vote_proposal_whitelist {
+    if let Some(wl) = &config.vote_proposal_whitelist {
         if !wl.contains(&info.sender) {
             return Err(ContractError::Unauthorized {});
         }
     }
 
-    // check voting expiration
-    if config.voting_period.is_expired(&env.block) {
-        return Err(ContractError::VotingPeriodExpired {});
+    // cohort members vote in their cohort window instead of the round voting period
+    match VOTER_COHORT.may_load(deps.storage, &info.sender)? {
+        Some(name) => {
+            let cohort = COHORTS.load(deps.storage, &name)?;
+            if let Some(start) = cohort.voting_start {
+                if !start.is_triggered(&env.block) {
+                    return Err(ContractError::VotingPeriodNotStarted {});
+                }
+            }
+            if cohort.voting_period.is_expired(&env.block) {
+                return Err(ContractError::VotingPeriodExpired {});
+            }
+        }
+        None => {
+            if config.voting_period.is_expired(&env.block) {
+                return Err(ContractError::VotingPeriodExpired {});
+            }
+        }
     }
 
-    // validate sent funds and funding denom matches
-    let fund = extract_budget_coin(&info.funds, &config.budget.denom)?;
+    let fund = match stipend_amount {
+        Some(amount) => spend_stipend(deps.storage, &config, &info, amount)?,
+        // validate sent funds and funding denom matches
+        None => extract_budget_coin(&info.funds, &config.budget.denom)?,
+    };
 
     // check existence of the proposal and collect funds in proposal
     let proposal = PROPOSALS.update(deps.storage, proposal_id.into(), |op| match op {
@@ -145,10 +447,25 @@ pub fn execute_vote_proposal(
         }
     })?;
 
+    // late votes and votes on opted out proposals still fund the proposal
+    // but are left out of matching
+    let matching_excluded = proposal.opt_out_matching
+        || matches!(config.vote_snapshot, Some(h) if env.block.height > h);
+
+    // discount matching contribution of young accounts
+    let matching_amount = match &config.tenure_weighting {
+        _ if matching_excluded => Uint128::zero(),
+        Some(tenure) => {
+            tenure_weighted_amount(deps.as_ref(), &env, tenure, &info.sender, fund.amount)?
+        }
+        None => fund.amount,
+    };
+
     let vote = Vote {
         proposal_id,
         voter: info.sender.to_string(),
         fund,
+        matching_amount,
     };
 
     // check sender did not voted on proposal
@@ -160,14 +477,236 @@ pub fn execute_vote_proposal(
     // save vote
     vote_key.save(deps.storage, &vote)?;
 
+    // update matching aggregates, distribution never iterates votes
+    if !matching_excluded {
+        let mut agg = PROPOSAL_AGG
+            .may_load(deps.storage, proposal_id)?
+            .unwrap_or_default();
+        agg.sum_sqrt += sqrt(vote.matching_amount);
+        agg.squared_sum = agg.sum_sqrt.checked_mul(agg.sum_sqrt)?;
+        agg.contributors += 1;
+        agg.total += vote.matching_amount;
+        PROPOSAL_AGG.save(deps.storage, proposal_id, &agg)?;
+    }
+
     Ok(Response::default().add_attributes(vec![
         attr("action", "vote_proposal"),
         attr("proposal_key", proposal_id.to_string()),
         attr("voter", vote.voter),
         attr("collected_fund", proposal.collected_funds),
+        attr("matching_amount", vote.matching_amount),
+        attr("matching_excluded", matching_excluded.to_string()),
     ]))
 }
 
+// spend from the voter stipend held by the contract
+fn spend_stipend(
+    storage: &mut dyn Storage,
+    config: &Config,
+    info: &MessageInfo,
+    amount: Uint128,
+) -> Result<Coin, ContractError> {
+    let stipend = config
+        .voter_stipend
+        .ok_or(ContractError::StipendNotEnabled {})?;
+    if !info.funds.is_empty() {
+        return Err(ContractError::WrongCoinSent {});
+    }
+    if amount.is_zero() {
+        return Err(ContractError::ZeroStipendAmount {});
+    }
+
+    let spent = STIPEND_SPENT
+        .may_load(storage, &info.sender)?
+        .unwrap_or_default();
+    let total = spent
+        .checked_add(amount)
+        .ok()
+        .filter(|total| *total <= stipend)
+        .ok_or(ContractError::StipendExceeded {
+            remaining: stipend - spent,
+        })?;
+    STIPEND_SPENT.save(storage, &info.sender, &total)?;
+    STIPEND_RESERVE.update(storage, |reserve| -> StdResult<_> { Ok(reserve - amount) })?;
+
+    Ok(coin(amount.u128(), &config.budget.denom))
+}
+
+// scale amount linearly by account age until maturity
+fn tenure_weighted_amount(
+    deps: Deps,
+    env: &Env,
+    tenure: &TenureWeighting,
+    voter: &Addr,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    let first_seen = match &tenure.oracle {
+        Some(oracle) => {
+            let res: FirstSeenResponse = deps.querier.query_wasm_smart(
+                oracle,
+                &TenureOracleQueryMsg::FirstSeen {
+                    address: voter.to_string(),
+                },
+            )?;
+            res.height
+        }
+        None => FIRST_SEEN.may_load(deps.storage, voter)?,
+    };
+    // unknown accounts are treated as brand new
+    let age = first_seen.map_or(0, |h| env.block.height.saturating_sub(h));
+
+    Ok(amount.multiply_ratio(age.min(tenure.maturity_blocks), tenure.maturity_blocks))
+}
+
+// voting ends once the round voting period and every cohort window expired
+fn voting_ended(storage: &dyn Storage, config: &Config, block: &BlockInfo) -> StdResult<bool> {
+    if !config.voting_period.is_expired(block) {
+        return Ok(false);
+    }
+    for cohort in COHORTS.range(storage, None, None, Order::Ascending) {
+        if !cohort?.1.voting_period.is_expired(block) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+pub fn execute_set_cohort(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: String,
+    members: Vec<String>,
+    voting_start: Option<Scheduled>,
+    voting_period: Expiration,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    // only admin can set cohorts
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if DISTRIBUTED.may_load(deps.storage)?.unwrap_or(false) {
+        return Err(ContractError::AlreadyDistributed {});
+    }
+
+    // voting can't be reopened once it ended
+    if voting_ended(deps.storage, &config, &env.block)? {
+        return Err(ContractError::VotingPeriodExpired {});
+    }
+
+    // windows must end in the future and start before they end
+    let valid_window = match (&voting_start, &voting_period) {
+        (_, Expiration::Never {}) => false,
+        (_, end) if end.is_expired(&env.block) => false,
+        (None, _) => true,
+        (Some(Scheduled::AtHeight(start)), Expiration::AtHeight(end)) => start < end,
+        (Some(Scheduled::AtTime(start)), Expiration::AtTime(end)) => start < end,
+        _ => false,
+    };
+    if !valid_window {
+        return Err(ContractError::InvalidCohortWindow {});
+    }
+
+    // members left out of a replaced cohort would get the round voting period back
+    let previous = COHORTS.may_load(deps.storage, &name)?;
+    if let Some(previous) = &previous {
+        if previous.voting_period.is_expired(&env.block) {
+            return Err(ContractError::VotingPeriodExpired {});
+        }
+    }
+
+    let mut members_addr = vec![];
+    for member in members {
+        let member = deps.api.addr_validate(&member)?;
+        if members_addr.contains(&member) {
+            continue;
+        }
+        // voters whose window already closed can't be given a new one
+        let current_window = match VOTER_COHORT.may_load(deps.storage, &member)? {
+            Some(other) => COHORTS.load(deps.storage, &other)?.voting_period,
+            None => config.voting_period,
+        };
+        if current_window.is_expired(&env.block) {
+            return Err(ContractError::VoterWindowExpired {
+                address: member.to_string(),
+            });
+        }
+        members_addr.push(member);
+    }
+
+    // members left out of the replaced cohort fall back to the round voting period
+    if let Some(previous) = previous {
+        for member in previous.members {
+            VOTER_COHORT.remove(deps.storage, &member);
+        }
+    }
+
+    for member in &members_addr {
+        // a voter belongs to a single cohort
+        if let Some(other) = VOTER_COHORT.may_load(deps.storage, member)? {
+            let mut previous = COHORTS.load(deps.storage, &other)?;
+            previous.members.retain(|m| m != member);
+            COHORTS.save(deps.storage, &other, &previous)?;
+        }
+        VOTER_COHORT.save(deps.storage, member, &name)?;
+    }
+    let cohort = Cohort {
+        members: members_addr,
+        voting_start,
+        voting_period,
+    };
+    COHORTS.save(deps.storage, &name, &cohort)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_cohort")
+        .add_attribute("name", name)
+        .add_attribute("members", cohort.members.len().to_string()))
+}
+
+pub fn execute_reduce_budget(
+    deps: DepsMut,
+    info: MessageInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    // only admin can reduce the budget
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if DISTRIBUTED.may_load(deps.storage)?.unwrap_or(false) {
+        return Err(ContractError::AlreadyDistributed {});
+    }
+
+    // allocations are committed at instantiation against the initial budget,
+    // a smaller budget would make the tally impossible to submit
+    if config.tally.is_some() {
+        return Err(ContractError::BudgetFixedByTally {});
+    }
+
+    if amount.is_zero() || amount > config.budget.amount {
+        return Err(ContractError::InvalidBudgetReduction {
+            budget: config.budget.amount,
+        });
+    }
+
+    // matching runs on the reduced budget
+    config.budget.amount -= amount;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: config.sponsor.to_string(),
+            amount: vec![coin(amount.u128(), &config.budget.denom)],
+        })
+        .add_attribute("action", "reduce_budget")
+        .add_attribute("amount", amount)
+        .add_attribute("budget", config.budget.amount))
+}
+
 pub fn execute_trigger_distribution(
     deps: DepsMut,
     env: Env,
@@ -180,68 +719,225 @@ pub fn execute_trigger_distribution(
         return Err(ContractError::Unauthorized {});
     }
 
-    // check voting period expiration
-    if !config.voting_period.is_expired(&env.block) {
+    // check voting period expiration, including cohort windows
+    if !voting_ended(deps.storage, &config, &env.block)? {
         return Err(ContractError::VotingPeriodNotExpired {});
     }
 
+    // distribution runs once
+    if DISTRIBUTED.may_load(deps.storage)?.unwrap_or(false) {
+        return Err(ContractError::AlreadyDistributed {});
+    }
+
+    // make sure the payouts are backed by the contract balance
+    let solvency = query_solvency(deps.as_ref(), env.clone())?;
+    if !solvency.shortfall.is_zero() {
+        return Err(ContractError::InsufficientFunds {
+            required: solvency.required,
+            available: solvency.available,
+            shortfall: solvency.shortfall,
+        });
+    }
+
     let query_proposals: StdResult<Vec<_>> = PROPOSALS
         .range(deps.storage, None, None, Order::Ascending)
         .collect();
 
     let proposals: Vec<Proposal> = query_proposals?.into_iter().map(|p| p.1).collect();
 
+    // nominated proposals can still register their fund address
+    if let Some(deadline) = config.claim_deadline {
+        if proposals.iter().any(|p| p.fund_address.is_none()) && !deadline.is_expired(&env.block) {
+            return Err(ContractError::ClaimDeadlineNotExpired {});
+        }
+    }
+
     let mut grants: Vec<RawGrant> = vec![];
     // collect proposals under grants
     for p in proposals {
-        let vote_query: StdResult<Vec<(Vec<u8>, Vote)>> = VOTES
-            .prefix(p.id.into())
-            .range(deps.storage, None, None, Order::Ascending)
-            .collect();
-
-        let mut votes: Vec<u128> = vec![];
-        for v in vote_query? {
-            votes.push(v.1.fund.amount.u128());
-        }
+        // opted out proposals only receive their collected funds
+        let agg = if p.opt_out_matching {
+            ProposalAggregate::default()
+        } else {
+            PROPOSAL_AGG
+                .may_load(deps.storage, p.id)?
+                .unwrap_or_default()
+        };
         let grant = RawGrant {
+            proposal_id: p.id,
             addr: p.fund_address,
-            funds: votes,
+            squared_sum: agg.squared_sum.u128(),
             collected_vote_funds: p.collected_funds.u128(),
         };
 
         grants.push(grant);
     }
 
-    let (distr_funds, leftover) = match config.algorithm {
-        QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism { .. } => {
-            calculate_clr(grants, Some(config.budget.amount.u128()))?
-        }
+    let (distr_funds, leftover) = match TALLY.may_load(deps.storage)? {
+        // off-chain computed rounds pay out the imported tally
+        Some(allocations) => apply_tally(grants, &allocations, config.budget.amount.u128()),
+        None if config.tally.is_some() => return Err(ContractError::TallyNotSubmitted {}),
+        None => match config.algorithm {
+            QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism { .. } => {
+                calculate_clr(grants, Some(config.budget.amount.u128()))?
+            }
+        },
     };
 
+    // unspent stipends are returned with the leftover
+    let stipend_reserve = STIPEND_RESERVE.may_load(deps.storage)?.unwrap_or_default();
+    let mut leftover = leftover + stipend_reserve.u128();
+
     let mut msgs = vec![];
-    for f in distr_funds {
-        msgs.push(CosmosMsg::Bank(BankMsg::Send {
-            to_address: f.addr.to_string(),
-            amount: vec![coin(f.grant + f.collected_vote_funds, &config.budget.denom)],
-        }));
+    let mut attrs = vec![];
+    // proposals without matching or votes have nothing to receive
+    for f in distr_funds
+        .into_iter()
+        .filter(|f| f.grant + f.collected_vote_funds > 0)
+    {
+        // allocations of unregistered proposals fall back to the leftover,
+        // including the direct contributions of their voters
+        let addr = match f.addr {
+            Some(addr) => addr,
+            None => {
+                leftover += f.grant + f.collected_vote_funds;
+                attrs.push(attr(
+                    format!("proposal_{}_unclaimed_match", f.proposal_id),
+                    f.grant.to_string(),
+                ));
+                attrs.push(attr(
+                    format!("proposal_{}_unclaimed_direct", f.proposal_id),
+                    f.collected_vote_funds.to_string(),
+                ));
+                continue;
+            }
+        };
+
+        if !config.split_payouts {
+            msgs.push(CosmosMsg::Bank(BankMsg::Send {
+                to_address: addr.to_string(),
+                amount: vec![coin(f.grant + f.collected_vote_funds, &config.budget.denom)],
+            }));
+            continue;
+        }
+
+        // matched funds and direct contributions are sent and labelled separately
+        // so grantee treasuries can book them apart
+        for (kind, amount) in [("match", f.grant), ("direct", f.collected_vote_funds)] {
+            if amount == 0 {
+                continue;
+            }
+            msgs.push(CosmosMsg::Bank(BankMsg::Send {
+                to_address: addr.to_string(),
+                amount: vec![coin(amount, &config.budget.denom)],
+            }));
+            attrs.push(attr(
+                format!("proposal_{}_{}", f.proposal_id, kind),
+                amount.to_string(),
+            ));
+        }
+    }
+
+    // bank rejects empty sends
+    if leftover > 0 {
+        let leftover_msg: CosmosMsg = CosmosMsg::Bank(BankMsg::Send {
+            to_address: config.leftover_addr.to_string(),
+            amount: vec![coin(leftover, config.budget.denom)],
+        });
+
+        msgs.push(leftover_msg);
     }
 
-    let leftover_msg: CosmosMsg = CosmosMsg::Bank(BankMsg::Send {
-        to_address: config.leftover_addr.to_string(),
-        amount: vec![coin(leftover, config.budget.denom)],
-    });
+    DISTRIBUTED.save(deps.storage, &true)?;
 
-    msgs.push(leftover_msg);
+    // commit to the payout set so integrations can verify the results
+    let commitment = results_commitment(&msgs)?;
+    #[cfg(feature = "stargate")]
+    if let Some(hook) = config.results_hook {
+        msgs.push(results_hook_msg(&hook, &commitment));
+    }
 
     Ok(Response::new()
         .add_messages(msgs)
-        .add_attribute("action", "trigger_distribution"))
+        .add_attribute("action", "trigger_distribution")
+        .add_attribute("results_commitment", hex::encode(commitment))
+        .add_attributes(attrs))
+}
+
+pub fn execute_submit_tally(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    allocations: Vec<TallyAllocation>,
+    submitted_hash: Binary,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let tally = config
+        .tally
+        .clone()
+        .ok_or(ContractError::TallyNotEnabled {})?;
+
+    // only the tally authority can submit
+    if info.sender != tally.authority {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // tally is final once voting ends
+    if !voting_ended(deps.storage, &config, &env.block)? {
+        return Err(ContractError::VotingPeriodNotExpired {});
+    }
+    if DISTRIBUTED.may_load(deps.storage)?.unwrap_or(false) {
+        return Err(ContractError::AlreadyDistributed {});
+    }
+
+    // submitted once, must match the hash committed at instantiate
+    if TALLY.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::TallyAlreadySubmitted {});
+    }
+    if submitted_hash != tally.tally_hash
+        || tally_hash(&allocations)? != tally.tally_hash.as_slice()
+    {
+        return Err(ContractError::TallyHashMismatch {});
+    }
+
+    // allocations are ordered by proposal id, matching proposals only
+    let mut last_id = 0;
+    let mut total = Uint128::zero();
+    for a in &allocations {
+        let proposal = PROPOSALS
+            .may_load(deps.storage, a.proposal_id)?
+            .ok_or(ContractError::ProposalNotFound {})?;
+        if a.proposal_id <= last_id || proposal.opt_out_matching {
+            return Err(ContractError::InvalidTallyAllocation {
+                proposal_id: a.proposal_id,
+            });
+        }
+        last_id = a.proposal_id;
+        total = total.checked_add(a.amount)?;
+    }
+    if total > config.budget.amount {
+        return Err(ContractError::TallyExceedsBudget {});
+    }
+
+    TALLY.save(deps.storage, &allocations)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "submit_tally")
+        .add_attribute("tally_hash", hex::encode(submitted_hash.as_slice()))
+        .add_attribute("allocated", total))
 }
 
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::ProposalByID { id } => to_binary(&query_proposal_id(deps, id)?),
         QueryMsg::AllProposals {} => to_binary(&query_all_proposals(deps)?),
+        QueryMsg::PoolUtilization {} => to_binary(&query_pool_utilization(deps)?),
+        QueryMsg::ContributionOf { voter, proposal_id } => {
+            to_binary(&query_contribution_of(deps, voter, proposal_id)?)
+        }
+        QueryMsg::OperatorOverview {} => to_binary(&query_operator_overview(deps, env)?),
+        QueryMsg::Solvency {} => to_binary(&query_solvency(deps, env)?),
+        QueryMsg::AlgorithmInfo {} => to_binary(&query_algorithm_info(deps)?),
     }
 }
 
@@ -260,16 +956,172 @@ fn query_all_proposals(deps: Deps) -> StdResult<AllProposalsResponse> {
     })
 }
 
+fn query_pool_utilization(deps: Deps) -> StdResult<PoolUtilizationResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let budget = config.budget.amount;
+
+    // in tally mode the grants are the submitted allocations
+    let tally = TALLY.may_load(deps.storage)?;
+    let awaiting_tally = config.tally.is_some() && tally.is_none();
+    let grants: Vec<(u64, Uint128)> = match tally {
+        Some(allocations) => allocations
+            .into_iter()
+            .map(|a| (a.proposal_id, a.amount))
+            .collect(),
+        None if awaiting_tally => vec![],
+        None => {
+            let aggs: StdResult<Vec<(u64, ProposalAggregate)>> = PROPOSAL_AGG
+                .range(deps.storage, None, None, Order::Ascending)
+                .collect();
+            let matched: Vec<(u64, Uint128)> = aggs?
+                .into_iter()
+                .map(|(id, agg)| (id, agg.squared_sum))
+                .collect();
+
+            // project the budget constrained grants
+            let weights: Vec<u128> = matched.iter().map(|m| m.1.u128()).collect();
+            matched
+                .iter()
+                .zip(apportion(&weights, budget.u128()))
+                .map(|(m, grant)| (m.0, Uint128::new(grant)))
+                .collect()
+        }
+    };
+
+    let mut consumed = Uint128::zero();
+    let mut largest: Option<(u64, Uint128)> = None;
+    for (id, grant) in grants {
+        consumed += grant;
+        // proposals projected no grant are never the largest
+        if grant.is_zero() {
+            continue;
+        }
+        match largest {
+            Some((_, l)) if l >= grant => {}
+            _ => largest = Some((id, grant)),
+        }
+    }
+
+    Ok(PoolUtilizationResponse {
+        utilization: ratio(consumed, budget),
+        projected_leftover: budget - consumed,
+        largest_proposal_id: largest.map(|l| l.0),
+        largest_proposal_share: ratio(largest.map_or(Uint128::zero(), |l| l.1), budget),
+        budget: config.budget,
+        awaiting_tally,
+    })
+}
+
+// single key lookup of a voter's contribution to a proposal
+fn query_contribution_of(
+    deps: Deps,
+    voter: String,
+    proposal_id: u64,
+) -> StdResult<ContributionResponse> {
+    let voter = deps.api.addr_validate(&voter)?;
+    let vote = VOTES.may_load(deps.storage, (proposal_id, voter.as_bytes()))?;
+    Ok(ContributionResponse { vote })
+}
+
+fn query_operator_overview(deps: Deps, env: Env) -> StdResult<OperatorOverviewResponse> {
+    let config = CONFIG.load(deps.storage)?;
+
+    let proposals: StdResult<Vec<(u64, Proposal)>> = PROPOSALS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect();
+    let proposals = proposals?;
+    let total_collected: Uint128 = proposals.iter().map(|p| p.1.collected_funds).sum();
+
+    let pool_balance = deps
+        .querier
+        .query_balance(&env.contract.address, &config.budget.denom)?;
+
+    let cohort_windows_remaining: StdResult<Vec<CohortWindowRemaining>> = COHORTS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|c| {
+            let (name, cohort) = c?;
+            Ok(CohortWindowRemaining {
+                name,
+                voting_period_remaining: remaining(&cohort.voting_period, &env.block),
+            })
+        })
+        .collect();
+
+    Ok(OperatorOverviewResponse {
+        proposal_count: proposals.len() as u64,
+        pool_balance,
+        total_collected,
+        distributed: DISTRIBUTED.may_load(deps.storage)?.unwrap_or(false),
+        proposal_period_remaining: remaining(&config.proposal_period, &env.block),
+        voting_period_remaining: remaining(&config.voting_period, &env.block),
+        cohort_windows_remaining: cohort_windows_remaining?,
+        budget: config.budget,
+    })
+}
+
+fn query_solvency(deps: Deps, env: Env) -> StdResult<SolvencyResponse> {
+    let config = CONFIG.load(deps.storage)?;
+
+    let proposals: StdResult<Vec<(u64, Proposal)>> = PROPOSALS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect();
+    let total_collected: Uint128 = proposals?.iter().map(|p| p.1.collected_funds).sum();
+    let stipend_reserve = STIPEND_RESERVE.may_load(deps.storage)?.unwrap_or_default();
+
+    let required = coin(
+        (config.budget.amount + total_collected + stipend_reserve).u128(),
+        &config.budget.denom,
+    );
+    let available = deps
+        .querier
+        .query_balance(&env.contract.address, &config.budget.denom)?;
+
+    Ok(SolvencyResponse {
+        shortfall: required.amount.saturating_sub(available.amount),
+        required,
+        available,
+    })
+}
+
+fn query_algorithm_info(deps: Deps) -> StdResult<AlgorithmInfoResponse> {
+    let config = CONFIG.load(deps.storage)?;
+
+    Ok(AlgorithmInfoResponse {
+        formula_version: config.algorithm.formula_version().to_string(),
+        algorithm: config.algorithm,
+        budget: config.budget,
+        tally: config.tally,
+        tenure_weighting: config.tenure_weighting,
+        vote_snapshot: config.vote_snapshot,
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::contract::{execute, instantiate, query_all_proposals, query_proposal_id};
+    use crate::contract::{
+        execute, instantiate, query_algorithm_info, query_all_proposals, query_contribution_of,
+        query_operator_overview, query_pool_utilization, query_proposal_id, query_solvency, reply,
+    };
     use crate::error::ContractError;
+    use crate::helper::tally_hash;
     use crate::matching::QuadraticFundingAlgorithm;
-    use crate::msg::{AllProposalsResponse, ExecuteMsg, InstantiateMsg};
-    use crate::state::{Proposal, PROPOSALS};
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coin, Addr, BankMsg, Binary, CosmosMsg, Uint128};
-    use cw_utils::Expiration;
+    use crate::msg::{
+        AlgorithmInfoResponse, AllProposalsResponse, AttestedResponse, CohortWindowRemaining,
+        ExecuteMsg, FirstSeenResponse, InstantiateMsg, KycQueryMsg, NotifierExecuteMsg,
+        ProjectRegistryQueryMsg, ProjectResponse, TallyConfigMsg, TenureOracleQueryMsg,
+        TenureWeightingMsg,
+    };
+    use crate::state::{
+        Proposal, RoundPhase, TallyAllocation, TallyConfig, TenureWeighting, COHORTS, CONFIG,
+        PROPOSALS, PROPOSAL_AGG, VOTES,
+    };
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MOCK_CONTRACT_ADDR};
+    use cosmwasm_std::{
+        attr, coin, from_binary, to_binary, Addr, BankMsg, Binary, ContractResult, CosmosMsg,
+        Decimal256, DepsMut, Env, Reply, StdError, SubMsg, SubMsgResult, SystemError, SystemResult,
+        Uint128, WasmMsg, WasmQuery,
+    };
+    use cw_utils::{Duration, Expiration, Scheduled};
 
     #[test]
     fn create_proposal() {
@@ -288,6 +1140,16 @@ mod tests {
             algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
                 parameter: "".to_string(),
             },
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
         };
 
         instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg.clone()).unwrap();
@@ -295,7 +1157,9 @@ mod tests {
             title: String::from("test"),
             description: String::from("test"),
             metadata: Some(b"test".into()),
-            fund_address: String::from("fund_address"),
+            fund_address: Some(String::from("fund_address")),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
 
         let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone());
@@ -326,6 +1190,16 @@ mod tests {
             algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
                 parameter: "".to_string(),
             },
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
         };
         instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg.clone()).unwrap();
 
@@ -339,22 +1213,124 @@ mod tests {
     }
 
     #[test]
-    fn vote_proposal() {
-        let mut env = mock_env();
+    fn create_proposal_registry() {
+        let env = mock_env();
         let info = mock_info("addr", &[coin(1000, "ucosm")]);
         let mut deps = mock_dependencies();
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "registry" => {
+                match from_binary(msg).unwrap() {
+                    ProjectRegistryQueryMsg::Project { id } if id == "project1" => {
+                        SystemResult::Ok(ContractResult::Ok(
+                            to_binary(&ProjectResponse { id }).unwrap(),
+                        ))
+                    }
+                    ProjectRegistryQueryMsg::Project { id } if id == "unreachable" => {
+                        SystemResult::Err(SystemError::NoSuchContract {
+                            addr: "registry".to_string(),
+                        })
+                    }
+                    _ => SystemResult::Ok(ContractResult::Err("not found".to_string())),
+                }
+            }
+            _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                kind: "wasm".to_string(),
+            }),
+        });
 
-        let mut init_msg = InstantiateMsg {
-            leftover_addr: String::from("addr"),
-            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
-                parameter: "".to_string(),
-            },
+        let init_msg = InstantiateMsg {
+            admin: String::from("addr"),
+            leftover_addr: String::from("addr"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: None,
+            voting_period: Expiration::AtHeight(env.block.height + 15),
+            proposal_period: Expiration::AtHeight(env.block.height + 10),
+            budget_denom: String::from("ucosm"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: Some(String::from("registry")),
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg).unwrap();
+
+        let create_msg = |project_id: &str| ExecuteMsg::CreateProposal {
+            title: String::from("test"),
+            description: String::from("test"),
+            metadata: None,
+            fund_address: Some(String::from("fund_address")),
+            registry_project_id: Some(project_id.to_string()),
+            opt_out_matching: false,
+        };
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            create_msg("project1"),
+        )
+        .unwrap();
+        let proposal = query_proposal_id(deps.as_ref(), 1).unwrap();
+        assert_eq!(proposal.registry_project_id, Some("project1".to_string()));
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            create_msg("project2"),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::ProjectNotRegistered { id }) => assert_eq!(id, "project2"),
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        // registry failures are not reported as unregistered projects
+        let res = execute(deps.as_mut(), env, info, create_msg("unreachable"));
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::Std(StdError::GenericErr { msg })) => {
+                assert!(msg.starts_with("Querier system error"))
+            }
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+    }
+
+    #[test]
+    fn vote_proposal() {
+        let mut env = mock_env();
+        let info = mock_info("addr", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+
+        let mut init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
             admin: String::from("addr"),
             create_proposal_whitelist: None,
             vote_proposal_whitelist: None,
             voting_period: Expiration::AtHeight(env.block.height + 15),
             proposal_period: Expiration::AtHeight(env.block.height + 10),
             budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
         };
         instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg.clone()).unwrap();
 
@@ -362,7 +1338,9 @@ mod tests {
             title: String::from("test"),
             description: String::from("test"),
             metadata: Some(Binary::from(b"test")),
-            fund_address: String::from("fund_address"),
+            fund_address: Some(String::from("fund_address")),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
 
         let res = execute(
@@ -373,7 +1351,10 @@ mod tests {
         );
         assert!(res.is_ok());
 
-        let msg = ExecuteMsg::VoteProposal { proposal_id: 1 };
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 1,
+            stipend_amount: None,
+        };
         let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         // success case
         match res {
@@ -381,6 +1362,12 @@ mod tests {
             e => panic!("unexpected error, got {}", e.unwrap_err()),
         }
 
+        // contribution lookup
+        let res = query_contribution_of(deps.as_ref(), String::from("addr"), 1).unwrap();
+        assert_eq!(res.vote.map(|v| v.fund), Some(coin(1000, "ucosm")));
+        let res = query_contribution_of(deps.as_ref(), String::from("other"), 1).unwrap();
+        assert_eq!(res.vote, None);
+
         // double vote prevention
         let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         match res {
@@ -414,6 +1401,331 @@ mod tests {
         }
     }
 
+    #[test]
+    fn vote_proposal_tenure_weighting() {
+        let mut env = mock_env();
+        let info = mock_info("addr", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+
+        let init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
+            admin: String::from("addr"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: None,
+            voting_period: Expiration::AtHeight(env.block.height + 150),
+            proposal_period: Expiration::AtHeight(env.block.height + 10),
+            budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: Some(TenureWeightingMsg {
+                oracle: None,
+                maturity_blocks: 100,
+            }),
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg).unwrap();
+
+        for title in ["proposal 1", "proposal 2", "proposal 3"] {
+            let msg = ExecuteMsg::CreateProposal {
+                title: String::from(title),
+                description: String::from("test"),
+                metadata: None,
+                fund_address: Some(String::from("fund_address")),
+                registry_project_id: None,
+                opt_out_matching: false,
+            };
+            execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+
+        // registering ahead of voting starts the tenure clock
+        let early_voter = mock_info("early_voter", &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            early_voter,
+            ExecuteMsg::Register {},
+        )
+        .unwrap();
+
+        // first interaction, brand new account does not count towards matching
+        let voter = mock_info("voter", &[coin(1000, "ucosm")]);
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 1,
+            stipend_amount: None,
+        };
+        execute(deps.as_mut(), env.clone(), voter.clone(), msg).unwrap();
+        let vote = VOTES.load(&deps.storage, (1, b"voter")).unwrap();
+        assert_eq!(vote.matching_amount, Uint128::zero());
+        // a zero aggregate projects no grant
+        let utilization = query_pool_utilization(deps.as_ref()).unwrap();
+        assert_eq!(utilization.largest_proposal_id, None);
+        assert_eq!(utilization.largest_proposal_share, Decimal256::zero());
+
+        // half way to maturity
+        env.block.height += 50;
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 2,
+            stipend_amount: None,
+        };
+        execute(deps.as_mut(), env.clone(), voter.clone(), msg).unwrap();
+        let vote = VOTES.load(&deps.storage, (2, b"voter")).unwrap();
+        assert_eq!(vote.matching_amount, Uint128::new(500));
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 1,
+            stipend_amount: None,
+        };
+        let early_voter = mock_info("early_voter", &[coin(1000, "ucosm")]);
+        execute(deps.as_mut(), env.clone(), early_voter, msg).unwrap();
+        let vote = VOTES.load(&deps.storage, (1, b"early_voter")).unwrap();
+        assert_eq!(vote.matching_amount, Uint128::new(500));
+
+        // matured account counts fully
+        env.block.height += 80;
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 3,
+            stipend_amount: None,
+        };
+        execute(deps.as_mut(), env.clone(), voter, msg).unwrap();
+        let vote = VOTES.load(&deps.storage, (3, b"voter")).unwrap();
+        assert_eq!(vote.matching_amount, Uint128::new(1000));
+    }
+
+    #[test]
+    fn vote_proposal_tenure_oracle() {
+        let mut env = mock_env();
+        env.block.height = 1000;
+        let info = mock_info("addr", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "oracle" => {
+                let height = match from_binary(msg).unwrap() {
+                    TenureOracleQueryMsg::FirstSeen { address } if address == "old_voter" => {
+                        Some(100)
+                    }
+                    TenureOracleQueryMsg::FirstSeen { address } if address == "new_voter" => {
+                        Some(975)
+                    }
+                    TenureOracleQueryMsg::FirstSeen { .. } => None,
+                };
+                SystemResult::Ok(ContractResult::Ok(
+                    to_binary(&FirstSeenResponse { height }).unwrap(),
+                ))
+            }
+            _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                kind: "wasm".to_string(),
+            }),
+        });
+
+        let init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
+            admin: String::from("addr"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: None,
+            voting_period: Expiration::AtHeight(env.block.height + 150),
+            proposal_period: Expiration::AtHeight(env.block.height + 10),
+            budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: Some(TenureWeightingMsg {
+                oracle: Some(String::from("oracle")),
+                maturity_blocks: 100,
+            }),
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg).unwrap();
+
+        let msg = ExecuteMsg::CreateProposal {
+            title: String::from("test"),
+            description: String::from("test"),
+            metadata: None,
+            fund_address: Some(String::from("fund_address")),
+            registry_project_id: None,
+            opt_out_matching: false,
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // account age comes from the oracle, not from the first vote
+        for (voter, matching_amount) in [("old_voter", 1000), ("new_voter", 250), ("unknown", 0)] {
+            let msg = ExecuteMsg::VoteProposal {
+                proposal_id: 1,
+                stipend_amount: None,
+            };
+            let info = mock_info(voter, &[coin(1000, "ucosm")]);
+            execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+            let vote = VOTES.load(&deps.storage, (1, voter.as_bytes())).unwrap();
+            assert_eq!(vote.matching_amount, Uint128::new(matching_amount));
+        }
+    }
+
+    #[test]
+    fn vote_proposal_after_snapshot() {
+        let mut env = mock_env();
+        let info = mock_info("addr", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+
+        let init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
+            admin: String::from("addr"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: None,
+            voting_period: Expiration::AtHeight(env.block.height + 15),
+            proposal_period: Expiration::AtHeight(env.block.height + 10),
+            budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: Some(env.block.height + 5),
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg).unwrap();
+
+        let msg = ExecuteMsg::CreateProposal {
+            title: String::from("test"),
+            description: String::from("test"),
+            metadata: None,
+            fund_address: Some(String::from("fund_address")),
+            registry_project_id: None,
+            opt_out_matching: false,
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 1,
+            stipend_amount: None,
+        };
+        let info = mock_info("voter1", &[coin(1000, "ucosm")]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap();
+        assert!(res.attributes.contains(&attr("matching_excluded", "false")));
+
+        // after the snapshot funds are collected but not matched
+        env.block.height += 6;
+        let info = mock_info("voter2", &[coin(1000, "ucosm")]);
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        assert!(res.attributes.contains(&attr("matching_excluded", "true")));
+
+        let vote = VOTES.load(&deps.storage, (1, b"voter2")).unwrap();
+        assert_eq!(vote.matching_amount, Uint128::zero());
+        let agg = PROPOSAL_AGG.load(&deps.storage, 1).unwrap();
+        assert_eq!(agg.contributors, 1);
+        assert_eq!(agg.total, Uint128::new(1000));
+        assert_eq!(agg.squared_sum, Uint128::new(961));
+        let proposal = query_proposal_id(deps.as_ref(), 1).unwrap();
+        assert_eq!(proposal.collected_funds, Uint128::new(2000));
+    }
+
+    #[test]
+    fn vote_proposal_stipend() {
+        let env = mock_env();
+        let info = mock_info("addr", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+
+        let init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
+            admin: String::from("addr"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: Some(vec![String::from("voter1"), String::from("voter2")]),
+            voting_period: Expiration::AtHeight(env.block.height + 15),
+            proposal_period: Expiration::AtHeight(env.block.height + 10),
+            budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: Some(Uint128::new(100)),
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg).unwrap();
+
+        // stipends are reserved out of the budget
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(config.budget, coin(800, "ucosm"));
+
+        for title in ["proposal 1", "proposal 2"] {
+            let msg = ExecuteMsg::CreateProposal {
+                title: String::from(title),
+                description: String::from("test"),
+                metadata: None,
+                fund_address: Some(String::from("fund_address")),
+                registry_project_id: None,
+                opt_out_matching: false,
+            };
+            execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+
+        let voter = mock_info("voter1", &[]);
+        let vote_msg = |proposal_id: u64, amount: u128| ExecuteMsg::VoteProposal {
+            proposal_id,
+            stipend_amount: Some(Uint128::new(amount)),
+        };
+        execute(deps.as_mut(), env.clone(), voter.clone(), vote_msg(1, 60)).unwrap();
+        let proposal = query_proposal_id(deps.as_ref(), 1).unwrap();
+        assert_eq!(proposal.collected_funds, Uint128::new(60));
+
+        let res = execute(deps.as_mut(), env.clone(), voter.clone(), vote_msg(2, 50));
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::StipendExceeded { remaining }) => {
+                assert_eq!(remaining, Uint128::new(40))
+            }
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+        // amounts that overflow the spent total are rejected the same way
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            voter.clone(),
+            vote_msg(2, u128::MAX),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::StipendExceeded { remaining }) => {
+                assert_eq!(remaining, Uint128::new(40))
+            }
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+        execute(deps.as_mut(), env.clone(), voter, vote_msg(2, 40)).unwrap();
+
+        // unspent stipends stay reserved
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(1000, "ucosm")]);
+        let solvency = query_solvency(deps.as_ref(), env).unwrap();
+        assert_eq!(solvency.required, coin(1000, "ucosm"));
+        assert_eq!(solvency.shortfall, Uint128::zero());
+    }
+
     #[test]
     fn trigger_distribution() {
         let env = mock_env();
@@ -432,6 +1744,16 @@ mod tests {
             voting_period: Expiration::AtHeight(env.block.height + 15),
             proposal_period: Expiration::AtHeight(env.block.height + 10),
             budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
         };
 
         instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg.clone()).unwrap();
@@ -441,7 +1763,9 @@ mod tests {
             title: String::from("proposal 1"),
             description: "".to_string(),
             metadata: Some(Binary::from(b"test")),
-            fund_address: String::from("fund_address1"),
+            fund_address: Some(String::from("fund_address1")),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         assert!(res.is_ok());
@@ -450,7 +1774,9 @@ mod tests {
             title: String::from("proposal 2"),
             description: "".to_string(),
             metadata: Some(Binary::from(b"test")),
-            fund_address: String::from("fund_address2"),
+            fund_address: Some(String::from("fund_address2")),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         assert!(res.is_ok());
@@ -459,7 +1785,9 @@ mod tests {
             title: String::from("proposal 3"),
             description: "".to_string(),
             metadata: Some(Binary::from(b"test")),
-            fund_address: String::from("fund_address3"),
+            fund_address: Some(String::from("fund_address3")),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         assert!(res.is_ok());
@@ -468,14 +1796,19 @@ mod tests {
             title: String::from("proposal 4"),
             description: "".to_string(),
             metadata: Some(Binary::from(b"test")),
-            fund_address: String::from("fund_address4"),
+            fund_address: Some(String::from("fund_address4")),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone());
         assert!(res.is_ok());
 
         // insert votes
         // proposal1
-        let msg = ExecuteMsg::VoteProposal { proposal_id: 1 };
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 1,
+            stipend_amount: None,
+        };
         let vote11_fund = 1200u128;
         let info = mock_info("address1", &[coin(vote11_fund, "ucosm")]);
         let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone());
@@ -493,7 +1826,10 @@ mod tests {
         let proposal1 = vote11_fund + vote12_fund + vote13_fund;
 
         // proposal2
-        let msg = ExecuteMsg::VoteProposal { proposal_id: 2 };
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 2,
+            stipend_amount: None,
+        };
 
         let vote21_fund = 30000u128;
         let info = mock_info("address4", &[coin(vote21_fund, "ucosm")]);
@@ -508,7 +1844,10 @@ mod tests {
         let proposal2 = vote21_fund + vote22_fund;
 
         // proposal3
-        let msg = ExecuteMsg::VoteProposal { proposal_id: 3 };
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 3,
+            stipend_amount: None,
+        };
         let vote31_fund = 230000u128;
         let info = mock_info("address6", &[coin(vote31_fund, "ucosm")]);
         let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone());
@@ -522,7 +1861,10 @@ mod tests {
         let proposal3 = vote31_fund + vote32_fund;
 
         // proposal4
-        let msg = ExecuteMsg::VoteProposal { proposal_id: 4 };
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 4,
+            stipend_amount: None,
+        };
         let vote41_fund = 100000u128;
         let info = mock_info("address8", &[coin(vote41_fund, "ucosm")]);
         let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone());
@@ -535,16 +1877,52 @@ mod tests {
         execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
         let proposal4 = vote41_fund + vote42_fund;
 
+        // projected pool usage before distribution
+        let utilization = query_pool_utilization(deps.as_ref()).unwrap();
+        assert_eq!(utilization.projected_leftover, Uint128::zero());
+        assert_eq!(utilization.utilization, Decimal256::one());
+        assert_eq!(utilization.largest_proposal_id, Some(3));
+        assert_eq!(
+            utilization.largest_proposal_share,
+            Decimal256::from_ratio(228537u128, budget)
+        );
+
         let trigger_msg = ExecuteMsg::TriggerDistribution {};
         let info = mock_info("admin", &[]);
         let mut env = mock_env();
         env.block.height += 1000;
-        let res = execute(deps.as_mut(), env.clone(), info, trigger_msg);
+
+        // contract balance does not cover pool and collected funds
+        let total_fund = proposal1 + proposal2 + proposal3 + proposal4 + budget;
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(total_fund - 10, "ucosm")]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            trigger_msg.clone(),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::InsufficientFunds { shortfall, .. }) => {
+                assert_eq!(shortfall, Uint128::new(10))
+            }
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(total_fund, "ucosm")]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            trigger_msg.clone(),
+        );
 
         let expected_msgs: Vec<CosmosMsg<_>> = vec![
             CosmosMsg::Bank(BankMsg::Send {
                 to_address: String::from("fund_address1"),
-                amount: vec![coin(106444u128, "ucosm")],
+                amount: vec![coin(106445u128, "ucosm")],
             }),
             CosmosMsg::Bank(BankMsg::Send {
                 to_address: String::from("fund_address2"),
@@ -558,21 +1936,20 @@ mod tests {
                 to_address: String::from("fund_address4"),
                 amount: vec![coin(196653u128, "ucosm")],
             }),
-            // left over msg
-            CosmosMsg::Bank(BankMsg::Send {
-                to_address: String::from("addr"),
-                amount: vec![coin(1u128, "ucosm")],
-            }),
+            // no left over msg, remainder units are matched
         ];
         match res {
-            Ok(_) => {}
+            Ok(res) => {
+                let msgs: Vec<CosmosMsg> = res.messages.into_iter().map(|m| m.msg).collect();
+                assert_eq!(msgs, expected_msgs);
+            }
             e => panic!("unexpected error, got {}", e.unwrap_err()),
         }
 
         // check total cash in and out
         let expected_msg_total_distr: u128 = expected_msgs
             .into_iter()
-            .map(|d: CosmosMsg<BankMsg>| -> u128 {
+            .map(|d: CosmosMsg| -> u128 {
                 match d {
                     CosmosMsg::Bank(BankMsg::Send { amount, .. }) => {
                         amount.iter().map(|c| c.amount.u128()).sum()
@@ -583,9 +1960,1242 @@ mod tests {
             .collect::<Vec<u128>>()
             .iter()
             .sum();
-        let total_fund = proposal1 + proposal2 + proposal3 + proposal4 + budget;
+        assert_eq!(total_fund, expected_msg_total_distr);
+
+        let overview = query_operator_overview(deps.as_ref(), env.clone()).unwrap();
+        assert!(overview.distributed);
+        assert_eq!(overview.proposal_count, 4);
+        assert_eq!(overview.total_collected.u128(), total_fund - budget);
+        assert_eq!(overview.voting_period_remaining, Some(Duration::Height(0)));
+
+        // distribution runs once
+        let res = execute(deps.as_mut(), env, info, trigger_msg);
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::AlreadyDistributed {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "stargate")]
+    fn trigger_distribution_results_hook() {
+        use crate::state::ResultsHook;
+        use cosmwasm_std::to_vec;
+        use sha2::{Digest, Sha256};
+
+        let mut env = mock_env();
+        let info = mock_info("admin", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+
+        let init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
+            admin: String::from("admin"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: None,
+            voting_period: Expiration::AtHeight(env.block.height + 15),
+            proposal_period: Expiration::AtHeight(env.block.height + 10),
+            budget_denom: String::from("ucosm"),
+            results_hook: Some(ResultsHook {
+                type_url: String::from("/test.MsgRecordResults"),
+                value: Binary::from(vec![0x08, 0x01]),
+                commitment_field: 2,
+            }),
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg).unwrap();
+
+        let msg = ExecuteMsg::CreateProposal {
+            title: String::from("test"),
+            description: String::from("test"),
+            metadata: None,
+            fund_address: Some(String::from("fund_address")),
+            registry_project_id: None,
+            opt_out_matching: false,
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 1,
+            stipend_amount: None,
+        };
+        let voter = mock_info("voter", &[coin(100, "ucosm")]);
+        execute(deps.as_mut(), env.clone(), voter, msg).unwrap();
+
+        env.block.height += 20;
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(1100, "ucosm")]);
+        let admin = mock_info("admin", &[]);
+        let res = execute(
+            deps.as_mut(),
+            env,
+            admin,
+            ExecuteMsg::TriggerDistribution {},
+        )
+        .unwrap();
+
+        let payouts = vec![CosmosMsg::Bank(BankMsg::Send {
+            to_address: String::from("fund_address"),
+            amount: vec![coin(1100u128, "ucosm")],
+        })];
+        let commitment = Sha256::digest(to_vec(&payouts).unwrap()).to_vec();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "trigger_distribution"),
+                attr("results_commitment", hex::encode(&commitment)),
+            ]
+        );
+
+        // hook message follows the payouts, with the commitment as field 2
+        let mut value = vec![0x08, 0x01, 0x12, 0x20];
+        value.extend_from_slice(&commitment);
+        let msgs: Vec<CosmosMsg> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(msgs[..1], payouts[..]);
+        assert_eq!(
+            msgs.last(),
+            Some(&CosmosMsg::Stargate {
+                type_url: String::from("/test.MsgRecordResults"),
+                value: Binary(value),
+            })
+        );
+        assert_eq!(msgs.len(), 2);
+    }
+
+    #[test]
+    fn trigger_distribution_opt_out_matching() {
+        let env = mock_env();
+        let info = mock_info("admin", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+
+        let init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
+            admin: String::from("admin"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: None,
+            voting_period: Expiration::AtHeight(env.block.height + 15),
+            proposal_period: Expiration::AtHeight(env.block.height + 10),
+            budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg).unwrap();
+
+        for (fund_address, opt_out_matching) in [("fund_address1", false), ("fund_address2", true)]
+        {
+            let msg = ExecuteMsg::CreateProposal {
+                title: String::from("test"),
+                description: String::from("test"),
+                metadata: None,
+                fund_address: Some(String::from(fund_address)),
+                registry_project_id: None,
+                opt_out_matching,
+            };
+            execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
 
-        assert_eq!(total_fund, expected_msg_total_distr)
+        for proposal_id in [1, 2] {
+            let msg = ExecuteMsg::VoteProposal {
+                proposal_id,
+                stipend_amount: None,
+            };
+            let info = mock_info("voter", &[coin(100, "ucosm")]);
+            execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        }
+
+        let mut env = mock_env();
+        env.block.height += 1000;
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(1200, "ucosm")]);
+        let info = mock_info("admin", &[]);
+        let res = execute(deps.as_mut(), env, info, ExecuteMsg::TriggerDistribution {}).unwrap();
+
+        // whole budget is matched to the proposal that did not opt out
+        let msgs: Vec<CosmosMsg> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: String::from("fund_address1"),
+                    amount: vec![coin(1100u128, "ucosm")],
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: String::from("fund_address2"),
+                    amount: vec![coin(100u128, "ucosm")],
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn trigger_distribution_split_payouts() {
+        let env = mock_env();
+        let info = mock_info("admin", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+
+        let init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
+            admin: String::from("admin"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: None,
+            voting_period: Expiration::AtHeight(env.block.height + 15),
+            proposal_period: Expiration::AtHeight(env.block.height + 10),
+            budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: true,
+            claim_deadline: None,
+            kyc_registry: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg).unwrap();
+
+        for (fund_address, opt_out_matching) in [("fund_address1", false), ("fund_address2", true)]
+        {
+            let msg = ExecuteMsg::CreateProposal {
+                title: String::from("test"),
+                description: String::from("test"),
+                metadata: None,
+                fund_address: Some(String::from(fund_address)),
+                registry_project_id: None,
+                opt_out_matching,
+            };
+            execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+
+        for proposal_id in [1, 2] {
+            let msg = ExecuteMsg::VoteProposal {
+                proposal_id,
+                stipend_amount: None,
+            };
+            let info = mock_info("voter", &[coin(100, "ucosm")]);
+            execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        }
+
+        let mut env = mock_env();
+        env.block.height += 1000;
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(1200, "ucosm")]);
+        let info = mock_info("admin", &[]);
+        let res = execute(deps.as_mut(), env, info, ExecuteMsg::TriggerDistribution {}).unwrap();
+
+        // match and direct contributions are separate sends,
+        // the opted out proposal only receives its contributions
+        let msgs: Vec<CosmosMsg> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: String::from("fund_address1"),
+                    amount: vec![coin(1000u128, "ucosm")],
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: String::from("fund_address1"),
+                    amount: vec![coin(100u128, "ucosm")],
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: String::from("fund_address2"),
+                    amount: vec![coin(100u128, "ucosm")],
+                }),
+            ]
+        );
+        assert_eq!(
+            res.attributes[2..],
+            [
+                attr("proposal_1_match", "1000"),
+                attr("proposal_1_direct", "100"),
+                attr("proposal_2_direct", "100"),
+            ]
+        );
+    }
+
+    #[test]
+    fn submit_tally() {
+        let env = mock_env();
+        let info = mock_info("admin", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+
+        let allocations = vec![
+            TallyAllocation {
+                proposal_id: 1,
+                amount: Uint128::new(600),
+            },
+            TallyAllocation {
+                proposal_id: 2,
+                amount: Uint128::new(300),
+            },
+        ];
+        let committed = Binary(tally_hash(&allocations).unwrap());
+
+        let init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
+            admin: String::from("admin"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: None,
+            voting_period: Expiration::AtHeight(env.block.height + 15),
+            proposal_period: Expiration::AtHeight(env.block.height + 10),
+            budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: Some(TallyConfigMsg {
+                authority: String::from("tally"),
+                tally_hash: committed.clone(),
+            }),
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg).unwrap();
+
+        for fund_address in ["fund_address1", "fund_address2"] {
+            let msg = ExecuteMsg::CreateProposal {
+                title: String::from("test"),
+                description: String::from("test"),
+                metadata: None,
+                fund_address: Some(String::from(fund_address)),
+                registry_project_id: None,
+                opt_out_matching: false,
+            };
+            execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 2,
+            stipend_amount: None,
+        };
+        let voter = mock_info("voter", &[coin(100, "ucosm")]);
+        execute(deps.as_mut(), env.clone(), voter, msg).unwrap();
+
+        // votes are not projected, payouts come from the tally
+        let utilization = query_pool_utilization(deps.as_ref()).unwrap();
+        assert!(utilization.awaiting_tally);
+        assert_eq!(utilization.utilization, Decimal256::zero());
+        assert_eq!(utilization.projected_leftover, Uint128::new(1000));
+        assert_eq!(utilization.largest_proposal_id, None);
+
+        // the committed allocations fix the budget before the tally is submitted
+        let admin = mock_info("admin", &[]);
+        let msg = ExecuteMsg::ReduceBudget {
+            amount: Uint128::new(200),
+        };
+        let res = execute(deps.as_mut(), env.clone(), admin.clone(), msg);
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::BudgetFixedByTally {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        let mut env = mock_env();
+        env.block.height += 1000;
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(1100, "ucosm")]);
+        let authority = mock_info("tally", &[]);
+
+        // distribution waits for the tally
+        let trigger_msg = ExecuteMsg::TriggerDistribution {};
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            admin.clone(),
+            trigger_msg.clone(),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::TallyNotSubmitted {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        let submit_msg = |allocations: Vec<TallyAllocation>| ExecuteMsg::SubmitTally {
+            allocations,
+            tally_hash: committed.clone(),
+        };
+
+        // only the authority can submit
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            admin.clone(),
+            submit_msg(allocations.clone()),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::Unauthorized {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        // allocations must match the committed hash
+        let mut tampered = allocations.clone();
+        tampered[0].amount = Uint128::new(700);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            authority.clone(),
+            submit_msg(tampered),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::TallyHashMismatch {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            authority.clone(),
+            submit_msg(allocations.clone()),
+        )
+        .unwrap();
+
+        let utilization = query_pool_utilization(deps.as_ref()).unwrap();
+        assert!(!utilization.awaiting_tally);
+        assert_eq!(utilization.utilization, Decimal256::percent(90));
+        assert_eq!(utilization.projected_leftover, Uint128::new(100));
+        assert_eq!(utilization.largest_proposal_id, Some(1));
+        assert_eq!(utilization.largest_proposal_share, Decimal256::percent(60));
+
+        // tally can not be replayed
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            authority,
+            submit_msg(allocations),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::TallyAlreadySubmitted {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        let res = execute(deps.as_mut(), env, admin, trigger_msg).unwrap();
+        let msgs: Vec<CosmosMsg> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: String::from("fund_address1"),
+                    amount: vec![coin(600u128, "ucosm")],
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: String::from("fund_address2"),
+                    amount: vec![coin(400u128, "ucosm")],
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: String::from("addr"),
+                    amount: vec![coin(100u128, "ucosm")],
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn phase_notifications() {
+        let mut env = mock_env();
+        let info = mock_info("admin", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+
+        let init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
+            admin: String::from("admin"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: None,
+            voting_period: Expiration::AtHeight(env.block.height + 15),
+            proposal_period: Expiration::AtHeight(env.block.height + 10),
+            budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: Some(String::from("notifier")),
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg).unwrap();
+
+        let notification = |phase: RoundPhase| -> SubMsg {
+            SubMsg::reply_on_error(
+                WasmMsg::Execute {
+                    contract_addr: String::from("notifier"),
+                    msg: to_binary(&NotifierExecuteMsg::PhaseChanged { phase }).unwrap(),
+                    funds: vec![],
+                },
+                1,
+            )
+            .with_gas_limit(200_000)
+        };
+
+        let msg = ExecuteMsg::CreateProposal {
+            title: String::from("test"),
+            description: String::from("test"),
+            metadata: None,
+            fund_address: Some(String::from("fund_address")),
+            registry_project_id: None,
+            opt_out_matching: false,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        assert!(res.messages.is_empty());
+
+        // first transaction after the proposal period notifies once
+        env.block.height += 10;
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 1,
+            stipend_amount: None,
+        };
+        let voter = mock_info("voter1", &[coin(100, "ucosm")]);
+        let res = execute(deps.as_mut(), env.clone(), voter, msg.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![notification(RoundPhase::ProposalPeriodEnded)]
+        );
+
+        let voter = mock_info("voter2", &[coin(100, "ucosm")]);
+        let res = execute(deps.as_mut(), env.clone(), voter, msg).unwrap();
+        assert!(res.messages.is_empty());
+
+        // distribution notifies voting end and finalization after the payouts
+        env.block.height += 10;
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(1200, "ucosm")]);
+        let admin = mock_info("admin", &[]);
+        let res = execute(
+            deps.as_mut(),
+            env,
+            admin,
+            ExecuteMsg::TriggerDistribution {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[1..],
+            [
+                notification(RoundPhase::VotingEnded),
+                notification(RoundPhase::DistributionFinalized),
+            ]
+        );
+    }
+
+    #[test]
+    fn phase_notification_failure() {
+        let mut deps = mock_dependencies();
+
+        // failed notifications are swallowed so the round continues
+        let failed = Reply {
+            id: 1,
+            result: SubMsgResult::Err("out of gas".to_string()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), failed).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(
+            res.attributes,
+            vec![attr("action", "notify_failed"), attr("error", "out of gas")]
+        );
+
+        let unknown = Reply {
+            id: 2,
+            result: SubMsgResult::Err("error".to_string()),
+        };
+        match reply(deps.as_mut(), mock_env(), unknown) {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::UnknownReplyId { id }) => assert_eq!(id, 2),
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+    }
+
+    #[test]
+    fn register_fund_address() {
+        let mut env = mock_env();
+        let info = mock_info("admin", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "kyc" => {
+                match from_binary(msg).unwrap() {
+                    KycQueryMsg::Attested { address } => SystemResult::Ok(ContractResult::Ok(
+                        to_binary(&AttestedResponse {
+                            attested: address == "attested_fund",
+                        })
+                        .unwrap(),
+                    )),
+                }
+            }
+            _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                kind: "wasm".to_string(),
+            }),
+        });
+
+        let init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
+            admin: String::from("admin"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: None,
+            voting_period: Expiration::AtHeight(env.block.height + 15),
+            proposal_period: Expiration::AtHeight(env.block.height + 10),
+            budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: Some(Expiration::AtHeight(env.block.height + 20)),
+            kyc_registry: Some(String::from("kyc")),
+        };
+        instantiate(deps.as_mut(), env.clone(), info, init_msg).unwrap();
+
+        // proposals 1 and 3 are nominated without a fund address
+        for fund_address in [None, Some(String::from("fund_address2")), None] {
+            let msg = ExecuteMsg::CreateProposal {
+                title: String::from("test"),
+                description: String::from("test"),
+                metadata: None,
+                fund_address,
+                registry_project_id: None,
+                opt_out_matching: false,
+            };
+            let info = mock_info("nominator", &[]);
+            execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        }
+        for proposal_id in [1, 2, 3] {
+            let msg = ExecuteMsg::VoteProposal {
+                proposal_id,
+                stipend_amount: None,
+            };
+            let info = mock_info("voter", &[coin(100, "ucosm")]);
+            execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        }
+
+        let register_msg = |proposal_id: u64, fund_address: &str| ExecuteMsg::RegisterFundAddress {
+            proposal_id,
+            fund_address: fund_address.to_string(),
+        };
+        let creator = mock_info("nominator", &[]);
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            creator.clone(),
+            register_msg(9, "attested_fund"),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::ProposalNotFound {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        // only the creator can register
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            register_msg(1, "attested_fund"),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::Unauthorized {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        // registered addresses must be attested
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            creator.clone(),
+            register_msg(1, "unattested_fund"),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::FundAddressNotAttested { address }) => {
+                assert_eq!(address, "unattested_fund")
+            }
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        // distribution waits for the claim deadline
+        env.block.height += 16;
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(1300, "ucosm")]);
+        let admin = mock_info("admin", &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            admin.clone(),
+            ExecuteMsg::TriggerDistribution {},
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::ClaimDeadlineNotExpired {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            creator.clone(),
+            register_msg(1, "attested_fund"),
+        )
+        .unwrap();
+        let proposal = query_proposal_id(deps.as_ref(), 1).unwrap();
+        assert_eq!(
+            proposal.fund_address,
+            Some(Addr::unchecked("attested_fund"))
+        );
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            creator.clone(),
+            register_msg(1, "attested_fund"),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::FundAddressAlreadyRegistered {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        env.block.height += 5;
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            creator,
+            register_msg(3, "attested_fund"),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::ClaimDeadlineExpired {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        // unclaimed proposal 3 allocation goes to the leftover address
+        let res = execute(
+            deps.as_mut(),
+            env,
+            admin,
+            ExecuteMsg::TriggerDistribution {},
+        )
+        .unwrap();
+        let msgs: Vec<CosmosMsg> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: String::from("attested_fund"),
+                    amount: vec![coin(434u128, "ucosm")],
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: String::from("fund_address2"),
+                    amount: vec![coin(433u128, "ucosm")],
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: String::from("addr"),
+                    amount: vec![coin(433u128, "ucosm")],
+                }),
+            ]
+        );
+        // the leftover includes the direct contributions to proposal 3
+        assert_eq!(
+            res.attributes[2..],
+            [
+                attr("proposal_3_unclaimed_match", "333"),
+                attr("proposal_3_unclaimed_direct", "100"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reduce_budget() {
+        let mut env = mock_env();
+        let info = mock_info("sponsor", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+
+        let init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
+            admin: String::from("admin"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: None,
+            voting_period: Expiration::AtHeight(env.block.height + 15),
+            proposal_period: Expiration::AtHeight(env.block.height + 10),
+            budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg).unwrap();
+
+        let msg = ExecuteMsg::CreateProposal {
+            title: String::from("test"),
+            description: String::from("test"),
+            metadata: None,
+            fund_address: Some(String::from("fund_address")),
+            registry_project_id: None,
+            opt_out_matching: false,
+        };
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::VoteProposal {
+            proposal_id: 1,
+            stipend_amount: None,
+        };
+        let voter = mock_info("voter", &[coin(100, "ucosm")]);
+        execute(deps.as_mut(), env.clone(), voter, msg).unwrap();
+
+        let reduce_msg = |amount: u128| ExecuteMsg::ReduceBudget {
+            amount: Uint128::new(amount),
+        };
+        let admin = mock_info("admin", &[]);
+
+        // only admin can reduce the budget
+        let res = execute(deps.as_mut(), env.clone(), info, reduce_msg(400));
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::Unauthorized {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        let res = execute(deps.as_mut(), env.clone(), admin.clone(), reduce_msg(1001));
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::InvalidBudgetReduction { budget }) => {
+                assert_eq!(budget, Uint128::new(1000))
+            }
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        // reduction is returned to the sponsor
+        let res = execute(deps.as_mut(), env.clone(), admin.clone(), reduce_msg(400)).unwrap();
+        let msgs: Vec<CosmosMsg> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                to_address: String::from("sponsor"),
+                amount: vec![coin(400u128, "ucosm")],
+            })]
+        );
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(config.budget, coin(600, "ucosm"));
+
+        // matching runs on the reduced budget
+        env.block.height += 20;
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(700, "ucosm")]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            admin.clone(),
+            ExecuteMsg::TriggerDistribution {},
+        )
+        .unwrap();
+        let msgs: Vec<CosmosMsg> = res.messages.into_iter().map(|m| m.msg).collect();
+        assert_eq!(
+            msgs,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                to_address: String::from("fund_address"),
+                amount: vec![coin(700u128, "ucosm")],
+            })]
+        );
+
+        let res = execute(deps.as_mut(), env, admin, reduce_msg(100));
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::AlreadyDistributed {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+    }
+
+    #[test]
+    fn vote_cohorts() {
+        let mut env = mock_env();
+        let height = env.block.height;
+        let info = mock_info("admin", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+
+        let init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+                parameter: "".to_string(),
+            },
+            admin: String::from("admin"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: None,
+            voting_period: Expiration::AtHeight(height + 15),
+            proposal_period: Expiration::AtHeight(height + 10),
+            budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), info.clone(), init_msg).unwrap();
+
+        let cohort_msg = |name: &str, member: &str, voting_start: Option<Scheduled>, end: u64| {
+            ExecuteMsg::SetCohort {
+                name: name.to_string(),
+                members: vec![member.to_string()],
+                voting_start,
+                voting_period: Expiration::AtHeight(end),
+            }
+        };
+        let admin = mock_info("admin", &[]);
+
+        // only admin can set cohorts
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            cohort_msg("early", "early_voter", None, height + 5),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::Unauthorized {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        // windows must end in the future and start before they end
+        let invalid_windows = vec![
+            (None, Expiration::AtHeight(height)),
+            (None, Expiration::Never {}),
+            (
+                Some(Scheduled::AtHeight(height + 5)),
+                Expiration::AtHeight(height + 5),
+            ),
+            (
+                Some(Scheduled::AtTime(env.block.time)),
+                Expiration::AtHeight(height + 5),
+            ),
+        ];
+        for (voting_start, voting_period) in invalid_windows {
+            let msg = ExecuteMsg::SetCohort {
+                name: "early".to_string(),
+                members: vec!["early_voter".to_string()],
+                voting_start,
+                voting_period,
+            };
+            match execute(deps.as_mut(), env.clone(), admin.clone(), msg) {
+                Ok(_) => panic!("expected error"),
+                Err(ContractError::InvalidCohortWindow {}) => {}
+                e => panic!("unexpected error, got {}", e.unwrap_err()),
+            }
+        }
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin.clone(),
+            cohort_msg("early", "early_voter", None, height + 5),
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            admin.clone(),
+            cohort_msg(
+                "late",
+                "late_voter",
+                Some(Scheduled::AtHeight(height + 12)),
+                height + 30,
+            ),
+        )
+        .unwrap();
+
+        let msg = ExecuteMsg::CreateProposal {
+            title: String::from("test"),
+            description: String::from("test"),
+            metadata: None,
+            fund_address: Some(String::from("fund_address")),
+            registry_project_id: None,
+            opt_out_matching: false,
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let vote = |deps: DepsMut, env: Env, voter: &str| {
+            let msg = ExecuteMsg::VoteProposal {
+                proposal_id: 1,
+                stipend_amount: None,
+            };
+            execute(deps, env, mock_info(voter, &[coin(100, "ucosm")]), msg)
+        };
+
+        match vote(deps.as_mut(), env.clone(), "late_voter") {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::VotingPeriodNotStarted {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+        vote(deps.as_mut(), env.clone(), "early_voter").unwrap();
+
+        // early cohort window ends before the round voting period
+        env.block.height = height + 6;
+        match vote(deps.as_mut(), env.clone(), "early_voter") {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::VotingPeriodExpired {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+        vote(deps.as_mut(), env.clone(), "voter").unwrap();
+
+        // a closed cohort can't be replaced, its members would vote again
+        let msg = ExecuteMsg::SetCohort {
+            name: "early".to_string(),
+            members: vec![],
+            voting_start: None,
+            voting_period: Expiration::AtHeight(height + 30),
+        };
+        match execute(deps.as_mut(), env.clone(), admin.clone(), msg) {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::VotingPeriodExpired {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        // late cohort votes after the round voting period
+        env.block.height = height + 16;
+        match vote(deps.as_mut(), env.clone(), "voter") {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::VotingPeriodExpired {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+        vote(deps.as_mut(), env.clone(), "late_voter").unwrap();
+
+        // overview lists the cohort windows next to the round voting period
+        let overview = query_operator_overview(deps.as_ref(), env.clone()).unwrap();
+        assert_eq!(overview.voting_period_remaining, Some(Duration::Height(0)));
+        assert_eq!(
+            overview.cohort_windows_remaining,
+            vec![
+                CohortWindowRemaining {
+                    name: "early".to_string(),
+                    voting_period_remaining: Some(Duration::Height(0)),
+                },
+                CohortWindowRemaining {
+                    name: "late".to_string(),
+                    voting_period_remaining: Some(Duration::Height(14)),
+                },
+            ]
+        );
+
+        // distribution waits for the late cohort window
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(1300, "ucosm")]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            admin.clone(),
+            ExecuteMsg::TriggerDistribution {},
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::VotingPeriodNotExpired {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        // voters whose window closed can't join a cohort that is still open
+        for (cohort, voter) in [("late", "early_voter"), ("extra", "voter")] {
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                admin.clone(),
+                cohort_msg(cohort, voter, None, height + 30),
+            );
+            match res {
+                Ok(_) => panic!("expected error"),
+                Err(ContractError::VoterWindowExpired { address }) => assert_eq!(address, voter),
+                e => panic!("unexpected error, got {}", e.unwrap_err()),
+            }
+        }
+
+        // voters move between cohorts, duplicated members are listed once
+        let msg = ExecuteMsg::SetCohort {
+            name: "later".to_string(),
+            members: vec!["late_voter".to_string(), "late_voter".to_string()],
+            voting_start: None,
+            voting_period: Expiration::AtHeight(height + 30),
+        };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        assert_eq!(
+            COHORTS.load(&deps.storage, "late").unwrap().members,
+            Vec::<Addr>::new()
+        );
+        assert_eq!(
+            COHORTS.load(&deps.storage, "later").unwrap().members,
+            vec![Addr::unchecked("late_voter")]
+        );
+
+        // members left out of a replaced cohort fall back to the round voting period
+        let msg = ExecuteMsg::SetCohort {
+            name: "later".to_string(),
+            members: vec![],
+            voting_start: None,
+            voting_period: Expiration::AtHeight(height + 30),
+        };
+        execute(deps.as_mut(), env.clone(), admin.clone(), msg).unwrap();
+        match vote(deps.as_mut(), env.clone(), "late_voter") {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::VotingPeriodExpired {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        // voting can't be reopened once every window ended
+        env.block.height = height + 31;
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            admin.clone(),
+            cohort_msg("later", "voter", None, height + 50),
+        );
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::VotingPeriodExpired {}) => {}
+            e => panic!("unexpected error, got {}", e.unwrap_err()),
+        }
+
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(1400, "ucosm")]);
+        execute(
+            deps.as_mut(),
+            env,
+            admin,
+            ExecuteMsg::TriggerDistribution {},
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn query_algorithm() {
+        let env = mock_env();
+        let info = mock_info("admin", &[coin(1000, "ucosm")]);
+        let mut deps = mock_dependencies();
+
+        let algorithm = QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
+            parameter: "".to_string(),
+        };
+        let mut init_msg = InstantiateMsg {
+            leftover_addr: String::from("addr"),
+            algorithm: algorithm.clone(),
+            admin: String::from("admin"),
+            create_proposal_whitelist: None,
+            vote_proposal_whitelist: None,
+            voting_period: Expiration::AtHeight(env.block.height + 15),
+            proposal_period: Expiration::AtHeight(env.block.height + 10),
+            budget_denom: String::from("ucosm"),
+            results_hook: None,
+            tenure_weighting: Some(TenureWeightingMsg {
+                oracle: None,
+                maturity_blocks: 100,
+            }),
+            vote_snapshot: Some(env.block.height + 12),
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
+        };
+        instantiate(deps.as_mut(), env.clone(), info, init_msg.clone()).unwrap();
+
+        let res = query_algorithm_info(deps.as_ref()).unwrap();
+        assert_eq!(
+            res,
+            AlgorithmInfoResponse {
+                algorithm,
+                formula_version: "clr-largest-remainder-v1".to_string(),
+                budget: coin(1000, "ucosm"),
+                tally: None,
+                tenure_weighting: Some(TenureWeighting {
+                    oracle: None,
+                    maturity_blocks: 100,
+                }),
+                vote_snapshot: Some(env.block.height + 12),
+            }
+        );
+
+        // tally commitment and tenure oracle are reported as configured
+        let mut deps = mock_dependencies();
+        let tally_hash = Binary::from(vec![7u8; 32]);
+        init_msg.tenure_weighting = Some(TenureWeightingMsg {
+            oracle: Some(String::from("oracle")),
+            maturity_blocks: 100,
+        });
+        init_msg.tally = Some(TallyConfigMsg {
+            authority: String::from("tally"),
+            tally_hash: tally_hash.clone(),
+        });
+        instantiate(
+            deps.as_mut(),
+            env,
+            mock_info("admin", &[coin(1000, "ucosm")]),
+            init_msg,
+        )
+        .unwrap();
+
+        let res = query_algorithm_info(deps.as_ref()).unwrap();
+        assert_eq!(
+            res.tally,
+            Some(TallyConfig {
+                authority: Addr::unchecked("tally"),
+                tally_hash,
+            })
+        );
+        assert_eq!(
+            res.tenure_weighting,
+            Some(TenureWeighting {
+                oracle: Some(Addr::unchecked("oracle")),
+                maturity_blocks: 100,
+            })
+        );
     }
 
     #[test]
@@ -597,8 +3207,11 @@ mod tests {
             title: "title".to_string(),
             description: "desc".to_string(),
             metadata: None,
-            fund_address: Addr::unchecked("proposal1"),
+            creator: Addr::unchecked("creator"),
+            fund_address: Some(Addr::unchecked("proposal1")),
             collected_funds: Uint128::zero(),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
 
         let err = PROPOSALS.save(&mut deps.storage, 1_u64.into(), &proposal);
@@ -619,8 +3232,11 @@ mod tests {
             title: "title".to_string(),
             description: "desc".to_string(),
             metadata: None,
-            fund_address: Addr::unchecked("proposal1"),
+            creator: Addr::unchecked("creator"),
+            fund_address: Some(Addr::unchecked("proposal1")),
             collected_funds: Uint128::zero(),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let _ = PROPOSALS.save(&mut deps.storage, 1_u64.into(), &proposal);
 
@@ -629,8 +3245,11 @@ mod tests {
             title: "title 2".to_string(),
             description: "desc".to_string(),
             metadata: None,
-            fund_address: Addr::unchecked("proposal2"),
+            creator: Addr::unchecked("creator"),
+            fund_address: Some(Addr::unchecked("proposal2")),
             collected_funds: Uint128::zero(),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let _ = PROPOSALS.save(&mut deps.storage, 2_u64.into(), &proposal1);
         let res = query_all_proposals(deps.as_ref()).unwrap();
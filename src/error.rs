@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Coin, OverflowError, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,6 +6,9 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Overflow(#[from] OverflowError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
@@ -21,6 +24,18 @@ pub enum ContractError {
     #[error("Voting period not expired")]
     VotingPeriodNotExpired {},
 
+    #[error("Distribution already triggered")]
+    AlreadyDistributed {},
+
+    #[error(
+        "Insufficient funds (required: {required}, available: {available}, shortfall: {shortfall})"
+    )]
+    InsufficientFunds {
+        required: Coin,
+        available: Coin,
+        shortfall: Uint128,
+    },
+
     #[error("Wrong coin sent")]
     WrongCoinSent {},
 
@@ -32,4 +47,85 @@ pub enum ContractError {
 
     #[error("CLR algorithm requires a budget constrain")]
     CLRConstrainRequired {},
+
+    #[error("Invalid results hook commitment field: {field}")]
+    InvalidResultsHookField { field: u32 },
+
+    #[error("Results hook requires the stargate feature")]
+    ResultsHookNotEnabled {},
+
+    #[error("Tenure maturity must be greater than zero")]
+    InvalidTenureMaturity {},
+
+    #[error("Voter stipend requires a vote whitelist")]
+    StipendRequiresWhitelist {},
+
+    #[error("Voter stipends exceed the budget")]
+    StipendExceedsBudget {},
+
+    #[error("Voter stipend not enabled")]
+    StipendNotEnabled {},
+
+    #[error("Stipend amount must be greater than zero")]
+    ZeroStipendAmount {},
+
+    #[error("Stipend exceeded (remaining: {remaining})")]
+    StipendExceeded { remaining: Uint128 },
+
+    #[error("Tally import not enabled")]
+    TallyNotEnabled {},
+
+    #[error("Tally already submitted")]
+    TallyAlreadySubmitted {},
+
+    #[error("Tally not submitted")]
+    TallyNotSubmitted {},
+
+    #[error("Tally hash must be a sha256 hash")]
+    InvalidTallyHash {},
+
+    #[error("Tally hash mismatch")]
+    TallyHashMismatch {},
+
+    #[error("Invalid tally allocation (proposal_id: {proposal_id})")]
+    InvalidTallyAllocation { proposal_id: u64 },
+
+    #[error("Tally allocations exceed the budget")]
+    TallyExceedsBudget {},
+
+    #[error("Budget is fixed by the committed tally")]
+    BudgetFixedByTally {},
+
+    #[error("Project registry not configured")]
+    ProjectRegistryNotConfigured {},
+
+    #[error("Project not registered (id: {id})")]
+    ProjectNotRegistered { id: String },
+
+    #[error("Fund address already registered")]
+    FundAddressAlreadyRegistered {},
+
+    #[error("Claim deadline expired")]
+    ClaimDeadlineExpired {},
+
+    #[error("Claim deadline not expired")]
+    ClaimDeadlineNotExpired {},
+
+    #[error("Fund address not attested (address: {address})")]
+    FundAddressNotAttested { address: String },
+
+    #[error("Invalid budget reduction (budget: {budget})")]
+    InvalidBudgetReduction { budget: Uint128 },
+
+    #[error("Voting period not started")]
+    VotingPeriodNotStarted {},
+
+    #[error("Invalid cohort voting window")]
+    InvalidCohortWindow {},
+
+    #[error("Voting window already closed (address: {address})")]
+    VoterWindowExpired { address: String },
+
+    #[error("Unknown reply id (id: {id})")]
+    UnknownReplyId { id: u64 },
 }
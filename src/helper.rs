@@ -1,5 +1,12 @@
 use crate::error::ContractError;
-use cosmwasm_std::Coin;
+#[cfg(feature = "stargate")]
+use crate::state::ResultsHook;
+use crate::state::TallyAllocation;
+#[cfg(feature = "stargate")]
+use cosmwasm_std::Binary;
+use cosmwasm_std::{to_vec, BlockInfo, Coin, CosmosMsg, StdResult};
+use cw_utils::{Duration, Expiration};
+use sha2::{Digest, Sha256};
 
 // extract budget coin validate against sent_funds.denom
 pub fn extract_budget_coin(sent_funds: &[Coin], denom: &str) -> Result<Coin, ContractError> {
@@ -15,12 +22,55 @@ pub fn extract_budget_coin(sent_funds: &[Coin], denom: &str) -> Result<Coin, Con
     Ok(sent_funds[0].clone())
 }
 
+// time left until expiration, None if it never expires
+pub fn remaining(expiration: &Expiration, block: &BlockInfo) -> Option<Duration> {
+    match expiration {
+        Expiration::AtHeight(h) => Some(Duration::Height(h.saturating_sub(block.height))),
+        Expiration::AtTime(t) => Some(Duration::Time(
+            t.seconds().saturating_sub(block.time.seconds()),
+        )),
+        Expiration::Never {} => None,
+    }
+}
+
+// sha256 of the json encoded payout messages, reproducible from the emitted messages
+pub fn results_commitment(msgs: &[CosmosMsg]) -> StdResult<Vec<u8>> {
+    Ok(Sha256::digest(&to_vec(msgs)?).to_vec())
+}
+
+// sha256 of the json encoded tally allocations
+pub fn tally_hash(allocations: &[TallyAllocation]) -> StdResult<Vec<u8>> {
+    Ok(Sha256::digest(&to_vec(allocations)?).to_vec())
+}
+
+// append commitment to the hook value as a length delimited protobuf field
+#[cfg(feature = "stargate")]
+pub fn results_hook_msg(hook: &ResultsHook, commitment: &[u8]) -> CosmosMsg {
+    let mut value = hook.value.to_vec();
+    encode_varint(&mut value, u64::from(hook.commitment_field) << 3 | 2);
+    encode_varint(&mut value, commitment.len() as u64);
+    value.extend_from_slice(commitment);
+    CosmosMsg::Stargate {
+        type_url: hook.type_url.clone(),
+        value: Binary(value),
+    }
+}
+
+#[cfg(feature = "stargate")]
+fn encode_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::helper::extract_budget_coin;
     use cosmwasm_std::coin;
-    use cosmwasm_std::testing::mock_info;
+    use cosmwasm_std::testing::{mock_env, mock_info};
 
     #[test]
     fn test_extract_funding_coin() {
@@ -41,4 +91,38 @@ mod tests {
             Err(err) => println!("{:?}", err),
         }
     }
+
+    #[test]
+    fn test_remaining() {
+        let block = mock_env().block;
+
+        let exp = Expiration::AtHeight(block.height + 10);
+        assert_eq!(remaining(&exp, &block), Some(Duration::Height(10)));
+        let exp = Expiration::AtHeight(block.height - 10);
+        assert_eq!(remaining(&exp, &block), Some(Duration::Height(0)));
+        let exp = Expiration::AtTime(block.time.plus_seconds(60));
+        assert_eq!(remaining(&exp, &block), Some(Duration::Time(60)));
+        assert_eq!(remaining(&Expiration::Never {}, &block), None);
+    }
+
+    #[test]
+    #[cfg(feature = "stargate")]
+    fn test_results_hook_msg() {
+        let hook = ResultsHook {
+            type_url: "/test.MsgResults".to_string(),
+            value: Binary::from(vec![0x0a, 0x01, 0x61]),
+            commitment_field: 16,
+        };
+        let commitment = [0xffu8; 32];
+
+        let mut expected = vec![0x0a, 0x01, 0x61, 0x82, 0x01, 0x20];
+        expected.extend_from_slice(&commitment);
+        assert_eq!(
+            results_hook_msg(&hook, &commitment),
+            CosmosMsg::Stargate {
+                type_url: "/test.MsgResults".to_string(),
+                value: Binary(expected),
+            }
+        );
+    }
 }
@@ -2,6 +2,10 @@ pub mod contract;
 mod error;
 mod helper;
 mod matching;
+#[cfg(feature = "library")]
+pub mod math;
+#[cfg(not(feature = "library"))]
+mod math;
 pub mod msg;
 pub mod state;
 
@@ -1,37 +1,68 @@
 use crate::error::ContractError;
+use crate::math::apportion;
+use crate::state::TallyAllocation;
 use cosmwasm_schema::cw_serde;
 use cosmwasm_std::Addr;
-use integer_sqrt::IntegerSquareRoot;
 
 #[cw_serde]
 pub enum QuadraticFundingAlgorithm {
     CapitalConstrainedLiberalRadicalism { parameter: String },
 }
 
+impl QuadraticFundingAlgorithm {
+    // identifies the exact payout math, bumped on any change to the formula
+    pub fn formula_version(&self) -> &'static str {
+        match self {
+            QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism { .. } => {
+                "clr-largest-remainder-v1"
+            }
+        }
+    }
+}
+
 #[cw_serde]
 pub struct RawGrant {
-    pub addr: Addr,
-    pub funds: Vec<u128>,
+    pub proposal_id: u64,
+    // None if the fund address was never registered
+    pub addr: Option<Addr>,
+    // square of the sum of square roots of the votes, aggregated at vote time
+    pub squared_sum: u128,
     pub collected_vote_funds: u128,
 }
 
 #[cw_serde]
 pub struct CalculatedGrant {
-    pub addr: Addr,
+    pub proposal_id: u64,
+    pub addr: Option<Addr>,
     pub grant: u128,
     pub collected_vote_funds: u128,
 }
 
 type LeftOver = u128;
 
+// The payout set is deterministic so independent verifiers can reproduce it:
+// - grants are returned in ascending proposal id order
+// - each grant gets floor(matched * budget / matched_total)
+// - indivisible remainder units go one each to the largest remainders,
+//   ties broken by the lower proposal id
 pub fn calculate_clr(
-    grants: Vec<RawGrant>,
+    mut grants: Vec<RawGrant>,
     budget: Option<u128>,
 ) -> Result<(Vec<CalculatedGrant>, LeftOver), ContractError> {
     // clr algorithm works with budget constrain
     if let Some(budget) = budget {
-        // calculate matches sum
-        let matched = calculate_matched_sum(grants);
+        grants.sort_by_key(|g| g.proposal_id);
+
+        // unconstrained matches are aggregated at vote time
+        let matched = grants
+            .into_iter()
+            .map(|g| CalculatedGrant {
+                proposal_id: g.proposal_id,
+                addr: g.addr,
+                grant: g.squared_sum,
+                collected_vote_funds: g.collected_vote_funds,
+            })
+            .collect();
 
         // constraint the grants by budget
         let constrained = constrain_by_budget(matched, budget);
@@ -48,29 +79,41 @@ pub fn calculate_clr(
     }
 }
 
-// takes square root of each fund, sums, then squares and returns u128
-fn calculate_matched_sum(grants: Vec<RawGrant>) -> Vec<CalculatedGrant> {
-    grants
+// pays the imported tally allocations instead of computing the matches,
+// grants are returned in ascending proposal id order
+pub fn apply_tally(
+    mut grants: Vec<RawGrant>,
+    allocations: &[TallyAllocation],
+    budget: u128,
+) -> (Vec<CalculatedGrant>, LeftOver) {
+    grants.sort_by_key(|g| g.proposal_id);
+    let allocated: Vec<CalculatedGrant> = grants
         .into_iter()
-        .map(|g| {
-            let sum_sqrts: u128 = g.funds.into_iter().map(|v| v.integer_sqrt()).sum();
-            CalculatedGrant {
-                addr: g.addr,
-                grant: sum_sqrts * sum_sqrts,
-                collected_vote_funds: g.collected_vote_funds,
-            }
+        .map(|g| CalculatedGrant {
+            proposal_id: g.proposal_id,
+            grant: allocations
+                .iter()
+                .find(|a| a.proposal_id == g.proposal_id)
+                .map_or(0, |a| a.amount.u128()),
+            addr: g.addr,
+            collected_vote_funds: g.collected_vote_funds,
         })
-        .collect()
+        .collect();
+
+    let allocated_sum: u128 = allocated.iter().map(|a| a.grant).sum();
+    (allocated, budget - allocated_sum)
 }
 
-// takes square root of each fund, sums, then squares and returns u128
+// scales matched grants to the budget
 fn constrain_by_budget(grants: Vec<CalculatedGrant>, budget: u128) -> Vec<CalculatedGrant> {
-    let raw_total: u128 = grants.iter().map(|g| g.grant).sum();
+    let matched: Vec<u128> = grants.iter().map(|g| g.grant).collect();
     grants
         .into_iter()
-        .map(|g| CalculatedGrant {
+        .zip(apportion(&matched, budget))
+        .map(|(g, grant)| CalculatedGrant {
+            proposal_id: g.proposal_id,
             addr: g.addr,
-            grant: (g.grant * budget) / raw_total,
+            grant,
             collected_vote_funds: g.collected_vote_funds,
         })
         .collect()
@@ -79,9 +122,16 @@ fn constrain_by_budget(grants: Vec<CalculatedGrant>, budget: u128) -> Vec<Calcul
 #[cfg(test)]
 mod tests {
     use crate::matching::{calculate_clr, CalculatedGrant, RawGrant};
+    use crate::math::sqrt;
     use crate::state::Proposal;
     use cosmwasm_std::{Addr, Uint128};
 
+    // takes square root of each fund, sums, then squares
+    fn squared_sum(funds: &[u128]) -> u128 {
+        let sum_sqrts: u128 = funds.iter().map(|v| sqrt(Uint128::new(*v)).u128()).sum();
+        sum_sqrts * sum_sqrts
+    }
+
     #[test]
     fn test_clr_1() {
         let proposal1 = Proposal {
@@ -89,32 +139,44 @@ mod tests {
             title: "proposal1".to_string(),
             description: "proposal1".to_string(),
             metadata: None,
-            fund_address: Addr::unchecked("proposal1"),
+            creator: Addr::unchecked("creator"),
+            fund_address: Some(Addr::unchecked("proposal1")),
             collected_funds: Uint128::zero(),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let proposal2 = Proposal {
             id: 2,
             title: "proposal2".to_string(),
             description: "proposal2".to_string(),
             metadata: None,
-            fund_address: Addr::unchecked("proposal2"),
+            creator: Addr::unchecked("creator"),
+            fund_address: Some(Addr::unchecked("proposal2")),
             collected_funds: Uint128::zero(),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let proposal3 = Proposal {
             id: 3,
             title: "proposal3".to_string(),
             description: "proposal3".to_string(),
             metadata: None,
-            fund_address: Addr::unchecked("proposal3"),
+            creator: Addr::unchecked("creator"),
+            fund_address: Some(Addr::unchecked("proposal3")),
             collected_funds: Uint128::zero(),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let proposal4 = Proposal {
             id: 4,
             title: "proposal4".to_string(),
             description: "proposal4".to_string(),
             metadata: None,
-            fund_address: Addr::unchecked("proposal4"),
+            creator: Addr::unchecked("creator"),
+            fund_address: Some(Addr::unchecked("proposal4")),
             collected_funds: Uint128::zero(),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let votes1 = vec![7200u128];
         let votes2 = vec![12345u128];
@@ -123,45 +185,53 @@ mod tests {
 
         let grants = vec![
             RawGrant {
+                proposal_id: proposal1.id,
                 addr: proposal1.fund_address.clone(),
-                funds: votes1.clone(),
+                squared_sum: squared_sum(&votes1),
                 collected_vote_funds: votes1.iter().sum(),
             },
             RawGrant {
+                proposal_id: proposal2.id,
                 addr: proposal2.fund_address.clone(),
-                funds: votes2.clone(),
+                squared_sum: squared_sum(&votes2),
                 collected_vote_funds: votes2.iter().sum(),
             },
             RawGrant {
+                proposal_id: proposal3.id,
                 addr: proposal3.fund_address.clone(),
-                funds: votes3.clone(),
+                squared_sum: squared_sum(&votes3),
                 collected_vote_funds: votes3.iter().sum(),
             },
             RawGrant {
+                proposal_id: proposal4.id,
                 addr: proposal4.fund_address.clone(),
-                funds: votes4.clone(),
+                squared_sum: squared_sum(&votes4),
                 collected_vote_funds: votes4.iter().sum(),
             },
         ];
         let expected = vec![
             CalculatedGrant {
+                proposal_id: proposal1.id,
                 addr: proposal1.fund_address,
-                grant: 84737u128,
+                grant: 84738u128,
                 collected_vote_funds: 7200u128,
             },
             CalculatedGrant {
+                proposal_id: proposal2.id,
                 addr: proposal2.fund_address,
                 grant: 147966u128,
                 collected_vote_funds: 12345u128,
             },
             CalculatedGrant {
+                proposal_id: proposal3.id,
                 addr: proposal3.fund_address,
                 grant: 52312u128,
                 collected_vote_funds: 4456u128,
             },
             CalculatedGrant {
+                proposal_id: proposal4.id,
                 addr: proposal4.fund_address,
-                grant: 714983u128,
+                grant: 714984u128,
                 collected_vote_funds: 60000u128,
             },
         ];
@@ -169,7 +239,7 @@ mod tests {
         match res {
             Ok(o) => {
                 assert_eq!(o.0, expected);
-                assert_eq!(o.1, 2)
+                assert_eq!(o.1, 0)
             }
             e => panic!("unexpected error, got {}", e.unwrap_err()),
         }
@@ -177,7 +247,7 @@ mod tests {
 
     // values got from https://wtfisqf.com/?grant=1200,44999,33&grant=30000,58999&grant=230000,100&grant=100000,5&match=550000
     //        expected   got
-    // grant1 60673.38   60212
+    // grant1 60673.38   60213
     // grant2 164749.05  164602
     // grant3 228074.05  228537
     // grant4 96503.53   96648
@@ -188,32 +258,44 @@ mod tests {
             title: "proposal1".to_string(),
             description: "proposal1".to_string(),
             metadata: None,
-            fund_address: Addr::unchecked("proposal1"),
+            creator: Addr::unchecked("creator"),
+            fund_address: Some(Addr::unchecked("proposal1")),
             collected_funds: Uint128::zero(),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let proposal2 = Proposal {
             id: 2,
             title: "proposal2".to_string(),
             description: "proposal2".to_string(),
             metadata: None,
-            fund_address: Addr::unchecked("proposal2"),
+            creator: Addr::unchecked("creator"),
+            fund_address: Some(Addr::unchecked("proposal2")),
             collected_funds: Uint128::zero(),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let proposal3 = Proposal {
             id: 3,
             title: "proposal3".to_string(),
             description: "proposal3".to_string(),
             metadata: None,
-            fund_address: Addr::unchecked("proposal3"),
+            creator: Addr::unchecked("creator"),
+            fund_address: Some(Addr::unchecked("proposal3")),
             collected_funds: Uint128::zero(),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let proposal4 = Proposal {
             id: 4,
             title: "proposal4".to_string(),
             description: "proposal4".to_string(),
             metadata: None,
-            fund_address: Addr::unchecked("proposal4"),
+            creator: Addr::unchecked("creator"),
+            fund_address: Some(Addr::unchecked("proposal4")),
             collected_funds: Uint128::zero(),
+            registry_project_id: None,
+            opt_out_matching: false,
         };
         let votes1 = vec![1200u128, 44999u128, 33u128];
         let votes2 = vec![30000u128, 58999u128];
@@ -222,43 +304,51 @@ mod tests {
 
         let grants = vec![
             RawGrant {
+                proposal_id: proposal1.id,
                 addr: proposal1.fund_address.clone(),
-                funds: votes1.clone(),
+                squared_sum: squared_sum(&votes1),
                 collected_vote_funds: votes1.iter().sum(),
             },
             RawGrant {
+                proposal_id: proposal2.id,
                 addr: proposal2.fund_address.clone(),
-                funds: votes2.clone(),
+                squared_sum: squared_sum(&votes2),
                 collected_vote_funds: votes2.iter().sum(),
             },
             RawGrant {
+                proposal_id: proposal3.id,
                 addr: proposal3.fund_address.clone(),
-                funds: votes3.clone(),
+                squared_sum: squared_sum(&votes3),
                 collected_vote_funds: votes3.iter().sum(),
             },
             RawGrant {
+                proposal_id: proposal4.id,
                 addr: proposal4.fund_address.clone(),
-                funds: votes4.clone(),
+                squared_sum: squared_sum(&votes4),
                 collected_vote_funds: votes4.iter().sum(),
             },
         ];
         let expected = vec![
             CalculatedGrant {
+                proposal_id: proposal1.id,
                 addr: proposal1.fund_address,
-                grant: 60212u128,
+                grant: 60213u128,
                 collected_vote_funds: votes1.iter().sum(),
             },
             CalculatedGrant {
+                proposal_id: proposal2.id,
                 addr: proposal2.fund_address,
                 grant: 164602u128,
                 collected_vote_funds: votes2.iter().sum(),
             },
             CalculatedGrant {
+                proposal_id: proposal3.id,
                 addr: proposal3.fund_address,
                 grant: 228537u128,
                 collected_vote_funds: votes3.iter().sum(),
             },
             CalculatedGrant {
+                proposal_id: proposal4.id,
                 addr: proposal4.fund_address,
                 grant: 96648u128,
                 collected_vote_funds: votes4.iter().sum(),
@@ -268,9 +358,24 @@ mod tests {
         match res {
             Ok(o) => {
                 assert_eq!(o.0, expected);
-                assert_eq!(o.1, 1)
+                assert_eq!(o.1, 0)
             }
             e => panic!("unexpected error, got {}", e.unwrap_err()),
         }
     }
+
+    #[test]
+    fn test_clr_deterministic_order_and_ties() {
+        let grant = |id: u64| RawGrant {
+            proposal_id: id,
+            addr: Some(Addr::unchecked(format!("proposal{}", id))),
+            squared_sum: squared_sum(&[100u128]),
+            collected_vote_funds: 100u128,
+        };
+        // input order does not matter, equal remainders go to lower proposal ids
+        let res = calculate_clr(vec![grant(3), grant(1), grant(2)], Some(101u128)).unwrap();
+        let grants: Vec<(u64, u128)> = res.0.iter().map(|g| (g.proposal_id, g.grant)).collect();
+        assert_eq!(grants, vec![(1, 34), (2, 34), (3, 33)]);
+        assert_eq!(res.1, 0);
+    }
 }
@@ -0,0 +1,129 @@
+// sqrt_256 and squared are only used by contracts depending on the library feature
+#![cfg_attr(not(feature = "library"), allow(dead_code))]
+
+use cosmwasm_std::{Decimal256, Isqrt, Uint128, Uint256};
+use std::convert::TryFrom;
+
+// floor of the square root
+pub fn sqrt(value: Uint128) -> Uint128 {
+    value.isqrt()
+}
+
+// floor of the square root, for values that do not fit in 128 bits
+pub fn sqrt_256(value: Uint256) -> Uint256 {
+    value.isqrt()
+}
+
+// square without overflow, u128::MAX squared fits in 256 bits
+pub fn squared(value: Uint128) -> Uint256 {
+    value.full_mul(value)
+}
+
+// numerator / denominator as a fraction, zero if the denominator is zero.
+// Decimal256 keeps 18 decimal places for any pair of 128 bit amounts
+pub fn ratio(numerator: Uint128, denominator: Uint128) -> Decimal256 {
+    if denominator.is_zero() {
+        Decimal256::zero()
+    } else {
+        Decimal256::from_ratio(numerator, denominator)
+    }
+}
+
+// splits budget proportionally to weights with the largest remainder method,
+// earlier weights win remainder ties. nothing is split if all weights are zero.
+// products and the weight total are computed in 256 bits so they can't overflow
+pub fn apportion(weights: &[u128], budget: u128) -> Vec<u128> {
+    let total: Uint256 = weights
+        .iter()
+        .fold(Uint256::zero(), |acc, w| acc + Uint256::from(*w));
+    if total.is_zero() {
+        return vec![0; weights.len()];
+    }
+
+    let mut shares = Vec::with_capacity(weights.len());
+    let mut remainders: Vec<(usize, Uint256)> = Vec::with_capacity(weights.len());
+    for (i, w) in weights.iter().enumerate() {
+        let product = Uint128::new(*w).full_mul(budget);
+        // a weight is at most the total, so its share is at most the budget
+        let share = Uint128::try_from(product / total).expect("share exceeds budget");
+        shares.push(share.u128());
+        remainders.push((i, product % total));
+    }
+    remainders.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    // less units than weights are left after flooring
+    let distributed: u128 = shares.iter().sum();
+    for (i, _) in remainders.into_iter().take((budget - distributed) as usize) {
+        shares[i] += 1;
+    }
+    shares
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::{apportion, ratio, sqrt, sqrt_256, squared};
+    use cosmwasm_std::{Decimal256, Uint128, Uint256};
+
+    #[test]
+    fn test_sqrt() {
+        assert_eq!(sqrt(Uint128::zero()), Uint128::zero());
+        assert_eq!(sqrt(Uint128::new(1)), Uint128::new(1));
+        assert_eq!(sqrt(Uint128::new(24)), Uint128::new(4));
+        assert_eq!(sqrt(Uint128::new(25)), Uint128::new(5));
+        assert_eq!(sqrt(Uint128::MAX), Uint128::new(u64::MAX as u128));
+
+        assert_eq!(sqrt_256(Uint256::zero()), Uint256::zero());
+        assert_eq!(sqrt_256(Uint256::from(1u128)), Uint256::from(1u128));
+        assert_eq!(sqrt_256(Uint256::MAX), Uint256::from(u128::MAX));
+        assert_eq!(sqrt_256(squared(Uint128::MAX)), Uint256::from(u128::MAX));
+    }
+
+    #[test]
+    fn test_squared() {
+        assert_eq!(squared(Uint128::zero()), Uint256::zero());
+        assert_eq!(squared(Uint128::new(1)), Uint256::from(1u128));
+        // (2^128 - 1)^2 = 2^256 - 2^129 + 1
+        assert_eq!(
+            squared(Uint128::MAX),
+            Uint256::MAX - Uint256::from(u128::MAX) - Uint256::from(u128::MAX)
+        );
+    }
+
+    #[test]
+    fn test_ratio() {
+        assert_eq!(ratio(Uint128::zero(), Uint128::zero()), Decimal256::zero());
+        assert_eq!(ratio(Uint128::new(1), Uint128::zero()), Decimal256::zero());
+        assert_eq!(ratio(Uint128::zero(), Uint128::new(1)), Decimal256::zero());
+        assert_eq!(ratio(Uint128::new(1), Uint128::new(1)), Decimal256::one());
+        assert_eq!(
+            ratio(Uint128::new(1), Uint128::new(4)),
+            Decimal256::percent(25)
+        );
+        assert_eq!(ratio(Uint128::MAX, Uint128::MAX), Decimal256::one());
+        assert_eq!(ratio(Uint128::new(1), Uint128::MAX), Decimal256::zero());
+        assert_eq!(
+            ratio(Uint128::MAX, Uint128::new(1)),
+            Decimal256::from_ratio(u128::MAX, 1u128)
+        );
+    }
+
+    #[test]
+    fn test_apportion() {
+        // largest remainder wins the indivisible unit
+        assert_eq!(apportion(&[1, 2], 10), vec![3, 7]);
+        // ties go to the earlier weight
+        assert_eq!(apportion(&[1, 1], 1), vec![1, 0]);
+        // nothing is split without weights
+        assert_eq!(apportion(&[0, 0], 10), vec![0, 0]);
+        assert_eq!(apportion(&[], 10), Vec::<u128>::new());
+        assert_eq!(apportion(&[1], 0), vec![0]);
+        assert_eq!(apportion(&[1], 1), vec![1]);
+        // weights and budget at the edge of u128 don't overflow
+        assert_eq!(apportion(&[u128::MAX], u128::MAX), vec![u128::MAX]);
+        assert_eq!(
+            apportion(&[u128::MAX, u128::MAX], u128::MAX),
+            vec![u128::MAX / 2 + 1, u128::MAX / 2]
+        );
+        assert_eq!(apportion(&[u128::MAX, 1], 1), vec![1, 0]);
+    }
+}
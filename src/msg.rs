@@ -1,9 +1,11 @@
 use crate::error::ContractError;
 use crate::matching::QuadraticFundingAlgorithm;
-use crate::state::Proposal;
+use crate::state::{
+    Proposal, ResultsHook, RoundPhase, TallyAllocation, TallyConfig, TenureWeighting, Vote,
+};
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Binary, Env};
-use cw_utils::Expiration;
+use cosmwasm_std::{Binary, Coin, Decimal256, Env, Uint128};
+use cw_utils::{Duration, Expiration, Scheduled};
 
 #[cw_serde]
 pub struct InstantiateMsg {
@@ -15,6 +17,29 @@ pub struct InstantiateMsg {
     pub proposal_period: Expiration,
     pub budget_denom: String,
     pub algorithm: QuadraticFundingAlgorithm,
+    pub results_hook: Option<ResultsHook>,
+    pub tenure_weighting: Option<TenureWeightingMsg>,
+    pub vote_snapshot: Option<u64>,
+    pub project_registry: Option<String>,
+    pub voter_stipend: Option<Uint128>,
+    pub tally: Option<TallyConfigMsg>,
+    pub notifier: Option<String>,
+    #[serde(default)]
+    pub split_payouts: bool,
+    pub claim_deadline: Option<Expiration>,
+    pub kyc_registry: Option<String>,
+}
+
+#[cw_serde]
+pub struct TenureWeightingMsg {
+    pub oracle: Option<String>,
+    pub maturity_blocks: u64,
+}
+
+#[cw_serde]
+pub struct TallyConfigMsg {
+    pub authority: String,
+    pub tally_hash: Binary,
 }
 
 impl InstantiateMsg {
@@ -27,6 +52,36 @@ impl InstantiateMsg {
         if self.voting_period.is_expired(&env.block) {
             return Err(ContractError::VotingPeriodExpired {});
         }
+        // protobuf field numbers are in range 1..=2^29-1
+        if let Some(hook) = &self.results_hook {
+            if hook.commitment_field == 0 || hook.commitment_field >= 1 << 29 {
+                return Err(ContractError::InvalidResultsHookField {
+                    field: hook.commitment_field,
+                });
+            }
+            if !cfg!(feature = "stargate") {
+                return Err(ContractError::ResultsHookNotEnabled {});
+            }
+        }
+        // stipends are granted to whitelisted voters only
+        if self.voter_stipend.is_some() && self.vote_proposal_whitelist.is_none() {
+            return Err(ContractError::StipendRequiresWhitelist {});
+        }
+        if let Some(tally) = &self.tally {
+            if tally.tally_hash.len() != 32 {
+                return Err(ContractError::InvalidTallyHash {});
+            }
+        }
+        if let Some(tenure) = &self.tenure_weighting {
+            if tenure.maturity_blocks == 0 {
+                return Err(ContractError::InvalidTenureMaturity {});
+            }
+        }
+        if let Some(deadline) = &self.claim_deadline {
+            if deadline.is_expired(&env.block) {
+                return Err(ContractError::ClaimDeadlineExpired {});
+            }
+        }
 
         Ok(())
     }
@@ -38,18 +93,52 @@ pub enum ExecuteMsg {
         title: String,
         description: String,
         metadata: Option<Binary>,
+        // left empty for nominated proposals, registered later by the creator.
+        // if it is never registered, both the match and the direct contributions
+        // of voters are sent to the leftover address
+        fund_address: Option<String>,
+        registry_project_id: Option<String>,
+        #[serde(default)]
+        opt_out_matching: bool,
+    },
+    RegisterFundAddress {
+        proposal_id: u64,
         fund_address: String,
     },
+    // returns part of the budget to the sponsor
+    ReduceBudget {
+        amount: Uint128,
+    },
+    // creates or replaces a cohort, members are moved from their previous cohort
+    SetCohort {
+        name: String,
+        members: Vec<String>,
+        voting_start: Option<Scheduled>,
+        voting_period: Expiration,
+    },
     VoteProposal {
         proposal_id: u64,
+        // spend from the voter stipend instead of sent funds
+        stipend_amount: Option<Uint128>,
     },
     TriggerDistribution {},
+    // starts the tenure clock of the sender when tenure weighting has no oracle
+    Register {},
+    SubmitTally {
+        allocations: Vec<TallyAllocation>,
+        tally_hash: Binary,
+    },
 }
 
 #[cw_serde]
 pub enum QueryMsg {
     ProposalByID { id: u64 },
     AllProposals {},
+    PoolUtilization {},
+    ContributionOf { voter: String, proposal_id: u64 },
+    OperatorOverview {},
+    Solvency {},
+    AlgorithmInfo {},
 }
 
 #[cw_serde]
@@ -57,9 +146,115 @@ pub struct AllProposalsResponse {
     pub proposals: Vec<Proposal>,
 }
 
+#[cw_serde]
+pub struct PoolUtilizationResponse {
+    pub budget: Coin,
+    // share of the budget the current votes would consume
+    pub utilization: Decimal256,
+    pub projected_leftover: Uint128,
+    pub largest_proposal_id: Option<u64>,
+    // share of the budget matched to the largest proposal
+    pub largest_proposal_share: Decimal256,
+    // payouts come from a tally that is not submitted yet, nothing is projected
+    pub awaiting_tally: bool,
+}
+
+#[cw_serde]
+pub struct ContributionResponse {
+    pub vote: Option<Vote>,
+}
+
+#[cw_serde]
+pub struct OperatorOverviewResponse {
+    pub proposal_count: u64,
+    pub budget: Coin,
+    // contract balance of the budget denom
+    pub pool_balance: Coin,
+    pub total_collected: Uint128,
+    pub distributed: bool,
+    // None if the period never expires
+    pub proposal_period_remaining: Option<Duration>,
+    // round voting period, voting ends after the cohort windows too
+    pub voting_period_remaining: Option<Duration>,
+    pub cohort_windows_remaining: Vec<CohortWindowRemaining>,
+}
+
+#[cw_serde]
+pub struct CohortWindowRemaining {
+    pub name: String,
+    pub voting_period_remaining: Option<Duration>,
+}
+
+#[cw_serde]
+pub struct SolvencyResponse {
+    // pool plus total collected funds and the unspent stipend reserve
+    pub required: Coin,
+    // contract balance of the budget denom
+    pub available: Coin,
+    pub shortfall: Uint128,
+}
+
+// everything needed to reproduce the payouts of the round
+#[cw_serde]
+pub struct AlgorithmInfoResponse {
+    pub algorithm: QuadraticFundingAlgorithm,
+    pub formula_version: String,
+    // budget the matches are constrained by
+    pub budget: Coin,
+    // payouts come from the imported tally instead of the algorithm,
+    // checked against the committed hash
+    pub tally: Option<TallyConfig>,
+    // None if matching amounts are not tenure weighted. without an oracle,
+    // account age is counted from the first interaction with this contract
+    pub tenure_weighting: Option<TenureWeighting>,
+    // votes after this height are excluded from matching
+    pub vote_snapshot: Option<u64>,
+}
+
+// query interface of the kyc registry
+#[cw_serde]
+pub enum KycQueryMsg {
+    Attested { address: String },
+}
+
+#[cw_serde]
+pub struct AttestedResponse {
+    pub attested: bool,
+}
+
+// execute interface of the notifier contract
+#[cw_serde]
+pub enum NotifierExecuteMsg {
+    PhaseChanged { phase: RoundPhase },
+}
+
+// query interface of the tenure oracle contract
+#[cw_serde]
+pub enum TenureOracleQueryMsg {
+    FirstSeen { address: String },
+}
+
+#[cw_serde]
+pub struct FirstSeenResponse {
+    pub height: Option<u64>,
+}
+
+// query interface of the project registry contract,
+// unknown projects are expected to return an error
+#[cw_serde]
+pub enum ProjectRegistryQueryMsg {
+    Project { id: String },
+}
+
+#[cw_serde]
+pub struct ProjectResponse {
+    pub id: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cosmwasm_std::from_slice;
     use cosmwasm_std::testing::mock_env;
 
     #[test]
@@ -78,6 +273,16 @@ mod tests {
             algorithm: QuadraticFundingAlgorithm::CapitalConstrainedLiberalRadicalism {
                 parameter: "".to_string(),
             },
+            results_hook: None,
+            tenure_weighting: None,
+            vote_snapshot: None,
+            project_registry: None,
+            voter_stipend: None,
+            tally: None,
+            notifier: None,
+            split_payouts: false,
+            claim_deadline: None,
+            kyc_registry: None,
         };
 
         let mut msg1 = msg.clone();
@@ -96,10 +301,110 @@ mod tests {
             Err(err) => println!("{:?}", err),
         }
 
-        let msg3 = msg.clone();
-        match msg3.validate(env) {
+        let mut msg3 = msg.clone();
+        msg3.results_hook = Some(ResultsHook {
+            type_url: "/test.MsgResults".to_string(),
+            value: Binary::default(),
+            commitment_field: 0,
+        });
+        match msg3.validate(env.clone()) {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::InvalidResultsHookField { field: 0 }) => {}
+            Err(err) => panic!("unexpected error, got {}", err),
+        }
+
+        // results hooks are only emitted by builds with the stargate feature
+        msg3.results_hook = Some(ResultsHook {
+            type_url: "/test.MsgResults".to_string(),
+            value: Binary::default(),
+            commitment_field: 1,
+        });
+        match (msg3.validate(env.clone()), cfg!(feature = "stargate")) {
+            (Ok(_), true) => {}
+            (Ok(_), false) => panic!("expected error"),
+            (Err(ContractError::ResultsHookNotEnabled {}), false) => {}
+            (Err(err), _) => panic!("unexpected error, got {}", err),
+        }
+
+        let mut msg4 = msg.clone();
+        msg4.tenure_weighting = Some(TenureWeightingMsg {
+            oracle: None,
+            maturity_blocks: 0,
+        });
+        match msg4.validate(env.clone()) {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::InvalidTenureMaturity {}) => {}
+            Err(err) => panic!("unexpected error, got {}", err),
+        }
+
+        let mut msg5 = msg.clone();
+        msg5.voter_stipend = Some(Uint128::new(100));
+        match msg5.validate(env.clone()) {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::StipendRequiresWhitelist {}) => {}
+            Err(err) => panic!("unexpected error, got {}", err),
+        }
+
+        let mut msg6 = msg.clone();
+        msg6.tally = Some(TallyConfigMsg {
+            authority: "tally".to_string(),
+            tally_hash: Binary::from(vec![0u8; 31]),
+        });
+        match msg6.validate(env.clone()) {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::InvalidTallyHash {}) => {}
+            Err(err) => panic!("unexpected error, got {}", err),
+        }
+
+        let mut msg7 = msg.clone();
+        msg7.claim_deadline = Some(Expiration::AtHeight(15));
+        match msg7.validate(env.clone()) {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::ClaimDeadlineExpired {}) => {}
+            Err(err) => panic!("unexpected error, got {}", err),
+        }
+
+        let msg8 = msg.clone();
+        match msg8.validate(env) {
             Ok(_) => {}
-            Err(err) => println!("{:?}", err),
+            Err(err) => panic!("unexpected error, got {}", err),
         }
     }
+
+    #[test]
+    fn create_proposal_defaults() {
+        // clients predating opt out still parse
+        let msg: ExecuteMsg = from_slice(
+            br#"{"create_proposal":{"title":"title","description":"desc","fund_address":"fund"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            msg,
+            ExecuteMsg::CreateProposal {
+                title: "title".to_string(),
+                description: "desc".to_string(),
+                metadata: None,
+                fund_address: Some("fund".to_string()),
+                registry_project_id: None,
+                opt_out_matching: false,
+            }
+        );
+    }
+
+    #[test]
+    fn instantiate_msg_defaults() {
+        // clients predating split payouts still parse
+        let msg: InstantiateMsg = from_slice(
+            br#"{
+                "admin": "admin",
+                "leftover_addr": "leftover",
+                "voting_period": {"at_height": 20},
+                "proposal_period": {"at_height": 10},
+                "budget_denom": "ucosm",
+                "algorithm": {"capital_constrained_liberal_radicalism": {"parameter": ""}}
+            }"#,
+        )
+        .unwrap();
+        assert!(!msg.split_payouts);
+    }
 }
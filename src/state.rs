@@ -3,7 +3,7 @@ use cosmwasm_schema::cw_serde;
 use cosmwasm_std::{Addr, Binary, Coin, Storage, Uint128};
 use cosmwasm_storage::{singleton, Singleton};
 use cw_storage_plus::{Item, Map};
-use cw_utils::Expiration;
+use cw_utils::{Expiration, Scheduled};
 
 #[cw_serde]
 pub struct Config {
@@ -11,27 +11,135 @@ pub struct Config {
     pub admin: Addr,
     // leftover coins from distribution sent to this address
     pub leftover_addr: Addr,
+    // sent the budget at instantiation, budget reductions are returned to it
+    pub sponsor: Addr,
     pub create_proposal_whitelist: Option<Vec<Addr>>,
     pub vote_proposal_whitelist: Option<Vec<Addr>>,
     pub voting_period: Expiration,
     pub proposal_period: Expiration,
     pub budget: Coin,
     pub algorithm: QuadraticFundingAlgorithm,
+    // stargate message emitted on distribution carrying the results commitment,
+    // only accepted by builds with the stargate feature
+    pub results_hook: Option<ResultsHook>,
+    // scale matching contributions by the voter's account age
+    pub tenure_weighting: Option<TenureWeighting>,
+    // votes after this height transfer funds but are excluded from matching
+    pub vote_snapshot: Option<u64>,
+    // external registry verifying canonical project identities
+    pub project_registry: Option<Addr>,
+    // budget denom granted to each whitelisted voter, spendable only on votes
+    pub voter_stipend: Option<Uint128>,
+    // pay out an off-chain computed tally instead of running the algorithm
+    pub tally: Option<TallyConfig>,
+    // contract notified on round phase changes
+    pub notifier: Option<Addr>,
+    // pay matched funds and direct contributions as separate sends
+    pub split_payouts: bool,
+    // fund addresses of nominated proposals must be registered before this
+    pub claim_deadline: Option<Expiration>,
+    // registry attesting fund addresses registered after creation
+    pub kyc_registry: Option<Addr>,
+}
+
+// authority allowed to submit the tally and the pre-committed
+// sha256 of the json encoded allocations
+#[cw_serde]
+pub struct TallyConfig {
+    pub authority: Addr,
+    pub tally_hash: Binary,
+}
+
+// matched amount of a proposal computed off-chain
+#[cw_serde]
+pub struct TallyAllocation {
+    pub proposal_id: u64,
+    pub amount: Uint128,
+}
+
+// imported tally, can be submitted once
+pub const TALLY: Item<Vec<TallyAllocation>> = Item::new("tally");
+
+// admin specified protobuf encoded message, the results commitment is appended
+// to `value` as a bytes field numbered `commitment_field`
+#[cw_serde]
+pub struct ResultsHook {
+    pub type_url: String,
+    pub value: Binary,
+    pub commitment_field: u32,
+}
+
+// contributions count fully towards matching once the account is `maturity_blocks` old.
+// account age is taken from the oracle if set, otherwise from the first interaction
+// with this contract. without an oracle a voter's first vote has no matching weight
+// unless they sent `Register {}` earlier
+#[cw_serde]
+pub struct TenureWeighting {
+    pub oracle: Option<Addr>,
+    pub maturity_blocks: u64,
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
 
+// set once distribution is triggered
+pub const DISTRIBUTED: Item<bool> = Item::new("distributed");
+
+#[cw_serde]
+pub enum RoundPhase {
+    ProposalPeriodEnded,
+    VotingEnded,
+    DistributionFinalized,
+}
+
+// voters with their own voting window, overriding the round voting period
+#[cw_serde]
+pub struct Cohort {
+    pub members: Vec<Addr>,
+    // voting opens immediately if not set
+    pub voting_start: Option<Scheduled>,
+    pub voting_period: Expiration,
+}
+
+pub const COHORTS: Map<&str, Cohort> = Map::new("cohorts");
+// cohort name of each cohort member
+pub const VOTER_COHORT: Map<&Addr, String> = Map::new("voter_cohort");
+
+// phases the notifier was already told about
+pub const NOTIFIED_PHASES: Item<Vec<RoundPhase>> = Item::new("notified_phases");
+
 #[cw_serde]
 pub struct Proposal {
     pub id: u64,
     pub title: String,
     pub description: String,
     pub metadata: Option<Binary>,
-    pub fund_address: Addr,
+    pub creator: Addr,
+    // None until the creator registers one for a nominated proposal
+    pub fund_address: Option<Addr>,
     pub collected_funds: Uint128,
+    // canonical project identity in the project registry, tracked across rounds
+    pub registry_project_id: Option<String>,
+    // receives direct contributions only, excluded from matching
+    pub opt_out_matching: bool,
 }
 
 pub const PROPOSALS: Map<u64, Proposal> = Map::new("proposal");
+
+// matching aggregates of a proposal, maintained at vote time
+#[cw_serde]
+#[derive(Default)]
+pub struct ProposalAggregate {
+    // sum of square roots of the matching amounts
+    pub sum_sqrt: Uint128,
+    // square of sum_sqrt, the unconstrained match
+    pub squared_sum: Uint128,
+    // votes counted in matching
+    pub contributors: u64,
+    // sum of the matching amounts
+    pub total: Uint128,
+}
+
+pub const PROPOSAL_AGG: Map<u64, ProposalAggregate> = Map::new("proposal_agg");
 pub const PROPOSAL_SEQ: &[u8] = b"proposal_seq";
 
 pub fn proposal_seq(storage: &mut dyn Storage) -> Singleton<u64> {
@@ -43,6 +151,16 @@ pub struct Vote {
     pub proposal_id: u64,
     pub voter: String,
     pub fund: Coin,
+    // portion of the fund counted by the matching algorithm
+    pub matching_amount: Uint128,
 }
 
 pub const VOTES: Map<(u64, &[u8]), Vote> = Map::new("votes");
+
+// stipend spent by each voter
+pub const STIPEND_SPENT: Map<&Addr, Uint128> = Map::new("stipend_spent");
+// unspent stipends held by the contract, returned as leftover on distribution
+pub const STIPEND_RESERVE: Item<Uint128> = Item::new("stipend_reserve");
+
+// height of the first interaction of an address with the contract
+pub const FIRST_SEEN: Map<&Addr, u64> = Map::new("first_seen");